@@ -1,12 +1,17 @@
-use crate::providers::types::{Scrobble, Track};
+use crate::providers::types::{Album, Scrobble, Track};
 use std::collections::HashMap;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct Statistics {
     pub top_tracks: Vec<(Track, usize)>,
+    /// Only filled from Last.fm charts; plays are not counted per artist.
+    pub top_artists: Vec<(String, usize)>,
+    /// Only filled from Last.fm charts, like `top_artists`.
+    pub top_albums: Vec<(Album, usize)>,
     pub total_plays: usize,
     pub unique_tracks: usize,
+    /// Shown above the top list when something is playing at upload time.
+    pub now_playing: Option<Track>,
 }
 
 pub fn compute_statistics(scrobbles: Vec<Scrobble>, top_n: usize) -> Statistics {
@@ -17,8 +22,11 @@ pub fn compute_statistics(scrobbles: Vec<Scrobble>, top_n: usize) -> Statistics
 
     Statistics {
         top_tracks: sorted_tracks,
+        top_artists: Vec::new(),
+        top_albums: Vec::new(),
         total_plays: scrobbles.len(),
         unique_tracks,
+        now_playing: None,
     }
 }
 
@@ -32,6 +40,6 @@ fn count_plays(scrobbles: &[Scrobble]) -> HashMap<Track, usize> {
 
 fn sort_by_play_count(counts: HashMap<Track, usize>) -> Vec<(Track, usize)> {
     let mut tracks: Vec<(Track, usize)> = counts.into_iter().collect();
-    tracks.sort_by(|a, b| b.1.cmp(&a.1));
+    tracks.sort_by_key(|t| std::cmp::Reverse(t.1));
     tracks
 }
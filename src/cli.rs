@@ -0,0 +1,111 @@
+use crate::config::Overrides;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "music-stats", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub config: ConfigArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Fetch scrobbles from the configured providers and print them
+    Fetch,
+    /// Print the top tracks without uploading them
+    Stats,
+    /// Upload already rendered statistics to the gist
+    Upload {
+        /// File holding the gist content; reads stdin when omitted
+        #[arg(long)]
+        input: Option<PathBuf>,
+
+        /// Print what would be uploaded instead of updating the gist
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Fetch, aggregate and upload to the gist (the default)
+    Run {
+        /// Print what would be uploaded instead of updating the gist
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check the configuration and report what is missing
+    Doctor,
+    /// Record plays from MPD into the `mpd.history` file until stopped
+    Listen,
+    /// Accept Plex and Jellyfin webhooks into the `webhook.history` file until stopped
+    Webhook,
+    /// Scrobble YouTube Music plays that are not on Last.fm yet
+    Sync {
+        /// Print the plays that would be scrobbled instead of sending them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Authorise music-stats with a service and save the credentials it gets
+    Auth {
+        #[command(subcommand)]
+        service: AuthService,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuthService {
+    /// Get a Last.fm session key and store it as `lastfm.session_key`
+    Lastfm,
+}
+
+/// Flags that override the environment variables read by `config`.
+#[derive(Debug, Default, Args)]
+pub struct ConfigArgs {
+    /// Config file to read instead of searching for music-stats.toml [env: MUSIC_STATS_CONFIG]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Gist to update [env: GIST_ID]
+    #[arg(long, global = true)]
+    pub gist_id: Option<String>,
+
+    /// GitHub token with the gist permission [env: GH_TOKEN]
+    #[arg(long, global = true)]
+    pub github_token: Option<String>,
+
+    /// Number of days to fetch [env: DAYS]
+    #[arg(long, global = true)]
+    pub days: Option<u64>,
+
+    /// Number of top tracks to show [env: TOP_N]
+    #[arg(long, global = true)]
+    pub top_n: Option<usize>,
+
+    /// Last.fm API key [env: LASTFM_API_KEY]
+    #[arg(long, global = true)]
+    pub lastfm_api_key: Option<String>,
+
+    /// Last.fm username [env: LASTFM_USERNAME]
+    #[arg(long, global = true)]
+    pub lastfm_username: Option<String>,
+
+    /// YouTube Music cookie [env: YOUTUBE_COOKIE]
+    #[arg(long, global = true)]
+    pub youtube_cookie: Option<String>,
+}
+
+impl ConfigArgs {
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            config_path: self.config.clone(),
+            gist_id: self.gist_id.clone(),
+            github_token: self.github_token.clone(),
+            days: self.days,
+            top_n: self.top_n,
+            lastfm_api_key: self.lastfm_api_key.clone(),
+            lastfm_username: self.lastfm_username.clone(),
+            youtube_cookie: self.youtube_cookie.clone(),
+        }
+    }
+}
@@ -1,142 +1,449 @@
 use crate::errors::Error;
+use crate::providers::lastfm_charts::ChartsConfig;
+use crate::providers::registry::Registry;
+use crate::sync::SyncConfig;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const CONFIG_FILENAME: &str = "music-stats.toml";
+
+/// Environment variables that do not follow the `section.key` → `SECTION_KEY` rule.
+const ENV_ALIASES: &[(&str, &str)] = &[("gist.token", "GH_TOKEN")];
 
 #[derive(Debug)]
 pub struct Config {
-    pub gist_id: String,
-    pub github_token: String,
-    pub provider: Provider,
+    pub gist_id: Option<String>,
+    pub github_token: Option<String>,
+    pub sources: Registry,
     pub days: u64,
     pub top_n: usize,
+    /// Set when `sync.ledger` is, for `music-stats sync`.
+    pub sync: Option<SyncConfig>,
+    /// Set when `lastfm.period` is; the top lists then come from Last.fm.
+    pub charts: Option<ChartsConfig>,
 }
 
-#[derive(Debug)]
-pub enum Provider {
-    LastFm(LastFmConfig),
-    YouTube(String),
-    Both {
-        lastfm: LastFmConfig,
-        youtube_cookie: String,
-    },
-}
-
-#[derive(Debug, Clone)]
-pub struct LastFmConfig {
-    pub api_key: String,
-    pub username: String,
+/// Values passed on the command line, which take precedence over the environment.
+#[derive(Debug, Default, Clone)]
+pub struct Overrides {
+    pub config_path: Option<PathBuf>,
+    pub gist_id: Option<String>,
+    pub github_token: Option<String>,
+    pub days: Option<u64>,
+    pub top_n: Option<usize>,
+    pub lastfm_api_key: Option<String>,
+    pub lastfm_username: Option<String>,
+    pub youtube_cookie: Option<String>,
 }
 
-impl Provider {
-    pub fn lastfm(&self) -> Option<&LastFmConfig> {
-        match self {
-            Provider::LastFm(config) => Some(config),
-            Provider::Both { lastfm, .. } => Some(lastfm),
-            _ => None,
-        }
+impl Config {
+    /// Returns the gist id and token, failing if either one is missing.
+    pub fn gist(&self) -> Result<(&str, &str), Error> {
+        let gist_id = self
+            .gist_id
+            .as_deref()
+            .ok_or_else(|| Error::MissingEnvVar {
+                variable: "GIST_ID".to_string(),
+            })?;
+        let github_token = self
+            .github_token
+            .as_deref()
+            .ok_or_else(|| Error::MissingEnvVar {
+                variable: "GH_TOKEN".to_string(),
+            })?;
+        Ok((gist_id, github_token))
     }
+}
 
-    pub fn youtube_cookie(&self) -> Option<&str> {
-        match self {
-            Provider::YouTube(cookie) => Some(cookie),
-            Provider::Both { youtube_cookie, .. } => Some(youtube_cookie),
+impl Overrides {
+    fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "gist.id" => self.gist_id.clone(),
+            "gist.token" => self.github_token.clone(),
+            "days" => self.days.map(|days| days.to_string()),
+            "top_n" => self.top_n.map(|top_n| top_n.to_string()),
+            "lastfm.api_key" => self.lastfm_api_key.clone(),
+            "lastfm.username" => self.lastfm_username.clone(),
+            "youtube.cookie" => self.youtube_cookie.clone(),
             _ => None,
         }
     }
 }
 
+/// Loads the configuration for a full run, which needs the gist credentials.
 pub fn load() -> Result<Config, Error> {
-    let gist_id = require_env("GIST_ID")?;
-    let github_token = require_env("GH_TOKEN")?;
-    let days = parse_env("DAYS", 7)?;
-    let top_n = parse_env("TOP_N", 5)?;
+    let config = load_with(&Overrides::default())?;
+    config.gist()?;
+    Ok(config)
+}
+
+/// Loads the configuration from every layer. When the same option is set in
+/// more than one place, command-line flags win over environment variables,
+/// which win over the config file, which wins over the built-in defaults.
+///
+/// The gist credentials are optional here; commands that upload call
+/// [`Config::gist`] to require them.
+pub fn load_with(overrides: &Overrides) -> Result<Config, Error> {
+    let settings = Settings::load(overrides)?;
+
+    let gist_id = settings.string("gist.id")?;
+    let github_token = settings.string("gist.token")?;
+    let days_setting: Option<u64> = settings.parse("days")?;
+    let days = days_setting.unwrap_or(7);
+    let top_n = settings.parse("top_n")?.unwrap_or(5);
 
-    let provider = load_provider()?;
+    let sources = Registry::from_settings(&settings)?;
+    let sync = SyncConfig::from_settings(&settings)?;
+    let charts = ChartsConfig::from_settings(&settings)?;
 
-    validate_config(days, top_n)?;
+    settings.reject_unknown_keys()?;
+    if sources.is_empty() {
+        return Err(Error::NoProviders);
+    }
+
+    if days == 0 {
+        return Err(settings.invalid("days", "must be greater than 0"));
+    }
+    if top_n == 0 {
+        return Err(settings.invalid("top_n", "must be greater than 0"));
+    }
+
+    if let Some(charts) = &charts {
+        let mut ignored: Vec<&str> = sources
+            .names()
+            .into_iter()
+            .filter(|name| *name != charts.lastfm.name)
+            .collect();
+        if days_setting.is_some() {
+            ignored.push("days");
+        }
+        if !ignored.is_empty() {
+            tracing::warn!(
+                "lastfm.period is set, so run and stats show Last.fm's charts and ignore {}",
+                ignored.join(", ")
+            );
+        }
+    }
 
     Ok(Config {
         gist_id,
         github_token,
-        provider,
+        sources,
         days,
         top_n,
+        sync,
+        charts,
     })
 }
 
-fn load_provider() -> Result<Provider, Error> {
-    let lastfm = try_load_lastfm();
-    let youtube = std::env::var("YOUTUBE_COOKIE")
-        .ok()
-        .filter(|s| !s.trim().is_empty());
+/// Returns the config file to read: the one passed with `--config` or
+/// `MUSIC_STATS_CONFIG`, otherwise the first `music-stats.toml` found in the
+/// current directory, `$XDG_CONFIG_HOME/music-stats/` and each of
+/// `$XDG_CONFIG_DIRS`.
+pub fn discover_config_file(explicit: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = explicit {
+        return Some(path.to_path_buf());
+    }
 
-    match (lastfm, youtube) {
-        (Some(lf), Some(yt)) => Ok(Provider::Both {
-            lastfm: lf,
-            youtube_cookie: yt,
-        }),
-        (Some(lf), None) => Ok(Provider::LastFm(lf)),
-        (None, Some(yt)) => Ok(Provider::YouTube(yt)),
-        (None, None) => Err(Error::NoProviders),
+    if let Some(path) = std::env::var_os("MUSIC_STATS_CONFIG").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
     }
-}
 
-fn try_load_lastfm() -> Option<LastFmConfig> {
-    let api_key = std::env::var("LASTFM_API_KEY")
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    let config_dirs = std::env::var("XDG_CONFIG_DIRS")
         .ok()
-        .filter(|s| !s.trim().is_empty());
-    let username = std::env::var("LASTFM_USERNAME")
-        .ok()
-        .filter(|s| !s.trim().is_empty());
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
 
-    match (api_key, username) {
-        (Some(key), Some(user)) => Some(LastFmConfig {
-            api_key: key,
-            username: user,
-        }),
-        _ => None,
-    }
+    let mut candidates = vec![PathBuf::from(CONFIG_FILENAME)];
+    candidates.extend(config_home.map(|dir| dir.join("music-stats").join(CONFIG_FILENAME)));
+    candidates.extend(
+        config_dirs
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Path::new(dir).join("music-stats").join(CONFIG_FILENAME)),
+    );
+
+    candidates.into_iter().find(|path| path.is_file())
 }
 
-fn require_env(key: &str) -> Result<String, Error> {
-    let value = std::env::var(key).map_err(|_| Error::MissingEnvVar {
-        variable: key.to_string(),
-    })?;
+/// Sets the option at dotted `key` in the config file, keeping the rest of
+/// the file as written. Creates `music-stats.toml` in the current directory
+/// when there is no config file yet. Returns the path written to.
+pub fn store(overrides: &Overrides, key: &str, value: &str) -> Result<PathBuf, Error> {
+    let path = discover_config_file(overrides.config_path.as_deref())
+        .unwrap_or_else(|| PathBuf::from(CONFIG_FILENAME));
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(Error::Input {
+                path: path.display().to_string(),
+                detail: e.to_string(),
+            });
+        }
+    };
+    let mut document: toml_edit::DocumentMut =
+        content
+            .parse()
+            .map_err(|e: toml_edit::TomlError| Error::InvalidConfig {
+                field: path.display().to_string(),
+                reason: e.message().to_string(),
+            })?;
 
-    if value.trim().is_empty() {
-        return Err(Error::MissingEnvVar {
-            variable: key.to_string(),
-        });
+    let (sections, name) = key
+        .rsplit_once('.')
+        .map_or((None, key), |(s, n)| (Some(s), n));
+    let mut table: &mut dyn toml_edit::TableLike = document.as_table_mut();
+    for section in sections.into_iter().flat_map(|s| s.split('.')) {
+        if table.get(section).is_none() {
+            table.insert(section, toml_edit::table());
+        }
+        table = table
+            .get_mut(section)
+            .and_then(toml_edit::Item::as_table_like_mut)
+            .ok_or_else(|| Error::InvalidConfig {
+                field: format!("{} in {}", section, path.display()),
+                reason: "expected a table".to_string(),
+            })?;
     }
+    table.insert(name, toml_edit::value(value));
 
-    Ok(value)
+    std::fs::write(&path, document.to_string()).map_err(|e| Error::Output {
+        path: path.display().to_string(),
+        detail: e.to_string(),
+    })?;
+    Ok(path)
 }
 
-fn parse_env<T: std::str::FromStr>(key: &str, default: T) -> Result<T, Error>
-where
-    T::Err: std::fmt::Display,
-{
-    match std::env::var(key) {
-        Ok(value) => value.parse().map_err(|e: T::Err| Error::InvalidConfig {
-            field: key.to_string(),
-            reason: e.to_string(),
-        }),
-        Err(_) => Ok(default),
-    }
+/// Merged view over flags, environment variables and the config file.
+///
+/// Options are addressed by their dotted path in the file, such as
+/// `lastfm.username`. The matching environment variable is the path upper-cased
+/// with dots turned into underscores (`LASTFM_USERNAME`).
+pub struct Settings {
+    overrides: Overrides,
+    path: Option<PathBuf>,
+    table: toml::Table,
+    used: RefCell<HashSet<String>>,
 }
 
-fn validate_config(days: u64, top_n: usize) -> Result<(), Error> {
-    if days == 0 {
-        return Err(Error::InvalidConfig {
-            field: "DAYS".to_string(),
-            reason: "must be greater than 0".to_string(),
-        });
+impl Settings {
+    pub fn load(overrides: &Overrides) -> Result<Self, Error> {
+        let mut settings = Self {
+            overrides: overrides.clone(),
+            path: None,
+            table: toml::Table::new(),
+            used: RefCell::new(HashSet::new()),
+        };
+
+        let Some(path) = discover_config_file(overrides.config_path.as_deref()) else {
+            return Ok(settings);
+        };
+
+        let content = std::fs::read_to_string(&path).map_err(|e| Error::Input {
+            path: path.display().to_string(),
+            detail: e.to_string(),
+        })?;
+        settings.table = content
+            .parse()
+            .map_err(|e: toml::de::Error| Error::InvalidConfig {
+                field: path.display().to_string(),
+                reason: e.message().to_string(),
+            })?;
+        settings.path = Some(path);
+
+        Ok(settings)
     }
 
-    if top_n == 0 {
-        return Err(Error::InvalidConfig {
-            field: "TOP_N".to_string(),
-            reason: "must be greater than 0".to_string(),
-        });
+    /// Resolves a string option, ignoring blank values in every layer.
+    pub fn string(&self, key: &str) -> Result<Option<String>, Error> {
+        self.used.borrow_mut().insert(key.to_string());
+
+        if let Some(value) = self.overrides.get(key).filter(|s| !s.trim().is_empty()) {
+            return Ok(Some(value));
+        }
+
+        if let Some(value) = std::env::var(env_name(key))
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+        {
+            return Ok(Some(value));
+        }
+
+        match self.get(key) {
+            Some(toml::Value::String(value)) => {
+                Ok(Some(value.clone()).filter(|s| !s.trim().is_empty()))
+            }
+            Some(_) => Err(self.invalid(key, "expected a string")),
+            None => Ok(None),
+        }
     }
 
-    Ok(())
+    /// Resolves an option that is parsed from its string form.
+    pub fn parse<T: FromStr>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.used.borrow_mut().insert(key.to_string());
+
+        let env = env_name(key);
+        let from_flag = self.overrides.get(key);
+        if let Some(value) = from_flag.or_else(|| std::env::var(&env).ok()) {
+            return value
+                .parse()
+                .map(Some)
+                .map_err(|e: T::Err| Error::InvalidConfig {
+                    field: env,
+                    reason: e.to_string(),
+                });
+        }
+
+        let raw = match self.get(key) {
+            Some(toml::Value::String(value)) => value.clone(),
+            Some(toml::Value::Integer(value)) => value.to_string(),
+            Some(toml::Value::Float(value)) => value.to_string(),
+            Some(toml::Value::Boolean(value)) => value.to_string(),
+            Some(_) => return Err(self.invalid(key, "expected a string, number or boolean")),
+            None => return Ok(None),
+        };
+
+        raw.parse()
+            .map(Some)
+            .map_err(|e: T::Err| self.invalid(key, &e.to_string()))
+    }
+
+    /// Like [`Settings::string`], but the option must be set.
+    pub fn require(&self, key: &str) -> Result<String, Error> {
+        self.string(key)?
+            .ok_or_else(|| self.invalid(key, "missing required option"))
+    }
+
+    /// Names of the tables nested under `key` in the config file, such as
+    /// `librefm` for `[lastfm.librefm]`. Named sections only exist in the file.
+    pub fn sections(&self, key: &str) -> Vec<String> {
+        match self.get(key).and_then(|value| value.as_table()) {
+            Some(table) => table
+                .iter()
+                .filter(|(_, value)| value.is_table())
+                .map(|(name, _)| name.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Resolves a list of paths. The environment variable separates them the
+    /// way `PATH` does; the file takes a single string or an array of strings.
+    pub fn paths(&self, key: &str) -> Result<Vec<PathBuf>, Error> {
+        self.used.borrow_mut().insert(key.to_string());
+
+        if let Some(value) = std::env::var_os(env_name(key)).filter(|v| !v.is_empty()) {
+            return Ok(std::env::split_paths(&value).collect());
+        }
+
+        match self.get(key) {
+            Some(toml::Value::String(value)) => Ok(vec![PathBuf::from(value)]),
+            Some(toml::Value::Array(items)) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| self.invalid(key, "expected a list of paths"))
+                })
+                .collect(),
+            Some(_) => Err(self.invalid(key, "expected a path or a list of paths")),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Resolves a list of strings. The environment variable separates them
+    /// with whitespace; the file takes a single string or an array of strings.
+    pub fn strings(&self, key: &str) -> Result<Vec<String>, Error> {
+        self.used.borrow_mut().insert(key.to_string());
+
+        if let Ok(value) = std::env::var(env_name(key)) {
+            return Ok(value.split_whitespace().map(String::from).collect());
+        }
+
+        match self.get(key) {
+            Some(toml::Value::String(value)) => Ok(vec![value.clone()]),
+            Some(toml::Value::Array(items)) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(String::from)
+                        .ok_or_else(|| self.invalid(key, "expected a list of strings"))
+                })
+                .collect(),
+            Some(_) => Err(self.invalid(key, "expected a string or a list of strings")),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&toml::Value> {
+        let mut parts = key.split('.');
+        let mut value = self.table.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_table()?.get(part)?;
+        }
+        Some(value)
+    }
+
+    /// Fails on the first option in the file that nothing asked for.
+    fn reject_unknown_keys(&self) -> Result<(), Error> {
+        let mut keys = Vec::new();
+        collect_keys("", &self.table, &mut keys);
+
+        let used = self.used.borrow();
+        match keys.into_iter().find(|key| !used.contains(key)) {
+            Some(key) => Err(self.invalid(&key, "unknown option")),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects a value that was read but cannot be used, naming the
+    /// environment variable when it was set there or by a flag, like
+    /// [`Settings::parse`] does.
+    pub fn invalid(&self, key: &str, reason: &str) -> Error {
+        let env = env_name(key);
+        let from_env = std::env::var(&env).is_ok_and(|value| !value.trim().is_empty());
+        let field = match &self.path {
+            _ if self.overrides.get(key).is_some() || from_env => env,
+            Some(path) => format!("{} in {}", key, path.display()),
+            None => key.to_string(),
+        };
+
+        Error::InvalidConfig {
+            field,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+fn env_name(key: &str) -> String {
+    ENV_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == key)
+        .map(|(_, env)| env.to_string())
+        .unwrap_or_else(|| key.replace('.', "_").to_uppercase())
+}
+
+fn collect_keys(prefix: &str, table: &toml::Table, keys: &mut Vec<String>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+
+        match value {
+            toml::Value::Table(section) => collect_keys(&key, section, keys),
+            _ => keys.push(key),
+        }
+    }
 }
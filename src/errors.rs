@@ -15,6 +15,46 @@ pub enum Error {
         url: String,
         body: String,
     },
+    /// An error Last.fm reported in the response body, which it sometimes
+    /// does with a 200 status.
+    LastFmApi {
+        service: String,
+        code: LastFmErrorCode,
+        message: String,
+    },
+    ListenBrainz {
+        status: u16,
+        url: String,
+        body: String,
+    },
+    Deezer {
+        status: u16,
+        url: String,
+        body: String,
+    },
+    Spotify {
+        status: u16,
+        url: String,
+        body: String,
+    },
+    Subsonic {
+        url: String,
+        detail: String,
+    },
+    Mpd {
+        address: String,
+        detail: String,
+    },
+    Webhook {
+        detail: String,
+    },
+    Command {
+        name: String,
+        detail: String,
+    },
+    Sync {
+        detail: String,
+    },
     YouTube {
         stage: String,
         detail: String,
@@ -28,6 +68,14 @@ pub enum Error {
         status: u16,
         body: String,
     },
+    Input {
+        path: String,
+        detail: String,
+    },
+    Output {
+        path: String,
+        detail: String,
+    },
 }
 
 impl fmt::Display for Error {
@@ -39,7 +87,10 @@ impl fmt::Display for Error {
             Error::NoProviders => {
                 write!(
                     f,
-                    "No music providers configured. Set LASTFM_API_KEY+LASTFM_USERNAME or YOUTUBE_COOKIE"
+                    "No music providers configured. Add one of the provider sections from the readme \
+                     ([lastfm], [youtube], [listenbrainz], [deezer], [spotify], [subsonic], [mpd], \
+                     [webhook], [command.<name>] or an import such as [spotify_export]) to \
+                     music-stats.toml, or set its options as environment variables"
                 )
             }
             Error::InvalidConfig { field, reason } => {
@@ -52,6 +103,71 @@ impl fmt::Display for Error {
                     status, url, body
                 )
             }
+            Error::LastFmApi {
+                service,
+                code,
+                message,
+            } => match code {
+                LastFmErrorCode::InvalidApiKey => {
+                    write!(
+                        f,
+                        "{} rejected the API key: {}. Check lastfm.api_key",
+                        service, message
+                    )
+                }
+                LastFmErrorCode::UserNotFound => {
+                    write!(
+                        f,
+                        "{} user not found: {}. Check lastfm.username",
+                        service, message
+                    )
+                }
+                LastFmErrorCode::RateLimited => {
+                    write!(f, "{} rate limit exceeded: {}", service, message)
+                }
+                LastFmErrorCode::TemporarilyUnavailable => {
+                    write!(f, "{} is temporarily unavailable: {}", service, message)
+                }
+                LastFmErrorCode::Other(code) => {
+                    write!(f, "{} API error {}: {}", service, code, message)
+                }
+            },
+            Error::ListenBrainz { status, url, body } => {
+                write!(
+                    f,
+                    "ListenBrainz API error (status {}): {} - Response: {}",
+                    status, url, body
+                )
+            }
+            Error::Deezer { status, url, body } => {
+                write!(
+                    f,
+                    "Deezer API error (status {}): {} - Response: {}",
+                    status, url, body
+                )
+            }
+            Error::Spotify { status, url, body } => {
+                write!(
+                    f,
+                    "Spotify API error (status {}): {} - Response: {}",
+                    status, url, body
+                )
+            }
+            Error::Subsonic { url, detail } => {
+                write!(f, "Subsonic API error from {}: {}", url, detail)
+            }
+            Error::Mpd { address, detail } => {
+                write!(f, "MPD error at {}: {}", address, detail)
+            }
+            Error::Webhook { detail } => {
+                write!(f, "Webhook error: {}", detail)
+            }
+            Error::Command { name, detail } => {
+                write!(f, "Command {} failed: {}", name, detail)
+            }
+            Error::Sync { detail } => {
+                write!(f, "Sync failed: {}", detail)
+            }
             Error::YouTube { stage, detail } => {
                 write!(f, "YouTube {} failed: {}", stage, detail)
             }
@@ -69,8 +185,48 @@ impl fmt::Display for Error {
                     gist_id, status, body
                 )
             }
+            Error::Input { path, detail } => {
+                write!(f, "Failed to read {}: {}", path, detail)
+            }
+            Error::Output { path, detail } => {
+                write!(f, "Failed to write {}: {}", path, detail)
+            }
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+/// The `error` codes of the Last.fm 2.0 API that we treat differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LastFmErrorCode {
+    /// 10, or 26 for a suspended key.
+    InvalidApiKey,
+    /// 6, which `user.getrecenttracks` returns for an unknown user.
+    UserNotFound,
+    /// 29.
+    RateLimited,
+    /// 8 (operation failed), 11 (service offline) or 16.
+    TemporarilyUnavailable,
+    Other(u32),
+}
+
+impl LastFmErrorCode {
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            10 | 26 => LastFmErrorCode::InvalidApiKey,
+            6 => LastFmErrorCode::UserNotFound,
+            29 => LastFmErrorCode::RateLimited,
+            8 | 11 | 16 => LastFmErrorCode::TemporarilyUnavailable,
+            other => LastFmErrorCode::Other(other),
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            LastFmErrorCode::RateLimited | LastFmErrorCode::TemporarilyUnavailable
+        )
+    }
+}
@@ -1,6 +1,8 @@
 //! Exposed for the tests
 pub mod aggregate;
+pub mod cli;
 pub mod config;
 pub mod errors;
 pub mod output;
 pub mod providers;
+pub mod sync;
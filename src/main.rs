@@ -1,11 +1,13 @@
+use clap::Parser;
+use music_stats::cli::{AuthService, Cli, Command};
+use music_stats::providers::lastfm_signed;
+use music_stats::{aggregate, config, errors, output, providers, sync};
+use std::io::Read;
 use std::time::Duration;
 use tracing_subscriber::EnvFilter;
 
-mod aggregate;
-mod config;
-mod errors;
-mod output;
-mod providers;
+/// How often `auth lastfm` checks whether the token has been approved.
+const AUTH_POLL_INTERVAL: Duration = Duration::from_secs(3);
 
 #[tokio::main]
 async fn main() {
@@ -15,27 +17,189 @@ async fn main() {
         .with_env_filter(
             EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
         )
+        .with_writer(std::io::stderr)
         .init();
 
-    if let Err(error) = run().await {
+    let cli = Cli::parse();
+
+    if let Err(error) = run(cli).await {
         eprintln!("Error: {}", error);
         std::process::exit(1);
     }
 }
 
-async fn run() -> Result<(), errors::Error> {
-    let config = config::load()?;
+async fn run(cli: Cli) -> Result<(), errors::Error> {
+    let overrides = cli.config.overrides();
     let client = build_http_client();
 
-    let scrobbles = fetch_scrobbles(&client, &config).await?;
-    tracing::info!("Fetched {} total scrobbles", scrobbles.len());
+    match cli.command.unwrap_or(Command::Run { dry_run: false }) {
+        Command::Fetch => {
+            let config = config::load_with(&overrides)?;
+            let mut scrobbles = fetch_scrobbles(&client, &config).await?;
+            scrobbles.sort_by_key(|s| s.played_at);
+            for scrobble in scrobbles {
+                println!(
+                    "{}\t{}\t{}",
+                    scrobble.played_at.to_rfc3339(),
+                    scrobble.track.artist,
+                    scrobble.track.title
+                );
+            }
+        }
+        Command::Stats => {
+            let config = config::load_with(&overrides)?;
+            let formatted = render(&client, &config).await?;
+            println!("{}", formatted);
+        }
+        Command::Upload { input, dry_run } => {
+            let config = config::load_with(&overrides)?;
+            let content = read_input(input)?;
+            publish(&client, &config, &content, dry_run).await?;
+        }
+        Command::Run { dry_run } => {
+            let config = config::load_with(&overrides)?;
+            if !dry_run {
+                config.gist()?;
+            }
+            let formatted = render(&client, &config).await?;
+            publish(&client, &config, &formatted, dry_run).await?;
+        }
+        Command::Doctor => {
+            if !doctor(&client, &overrides).await {
+                std::process::exit(1);
+            }
+        }
+        Command::Listen => {
+            let settings = config::Settings::load(&overrides)?;
+            let mpd = providers::mpd::MpdConfig::from_settings(&settings)?
+                .ok_or_else(|| settings.invalid("mpd.history", "missing required option"))?;
+            providers::mpd::listen(&mpd).await?;
+        }
+        Command::Webhook => {
+            let settings = config::Settings::load(&overrides)?;
+            let webhook = providers::webhook::WebhookConfig::from_settings(&settings)?
+                .ok_or_else(|| settings.invalid("webhook.history", "missing required option"))?;
+            providers::webhook::listen(webhook).await?;
+        }
+        Command::Sync { dry_run } => {
+            let config = config::load_with(&overrides)?;
+            run_sync(&client, &config, dry_run).await?;
+        }
+        Command::Auth {
+            service: AuthService::Lastfm,
+        } => auth_lastfm(&client, &overrides).await?,
+    }
 
-    let statistics = aggregate::compute_statistics(scrobbles, config.top_n);
-    let formatted = output::format::format_statistics(&statistics);
+    Ok(())
+}
 
-    output::github::upload_gist(&client, &config.gist_id, &config.github_token, &formatted).await?;
+async fn render(
+    client: &reqwest::Client,
+    config: &config::Config,
+) -> Result<String, errors::Error> {
+    let mut statistics = match &config.charts {
+        Some(charts) => {
+            providers::lastfm_charts::fetch_statistics(client, charts, config.top_n).await?
+        }
+        None => {
+            let scrobbles = fetch_scrobbles(client, config).await?;
+            tracing::info!("Fetched {} total scrobbles", scrobbles.len());
+            aggregate::compute_statistics(scrobbles, config.top_n)
+        }
+    };
+    statistics.now_playing = config.sources.now_playing(client).await;
+    Ok(output::format::format_statistics(&statistics))
+}
+
+async fn publish(
+    client: &reqwest::Client,
+    config: &config::Config,
+    content: &str,
+    dry_run: bool,
+) -> Result<(), errors::Error> {
+    if dry_run {
+        println!("{}", content);
+        println!();
+        println!("{}", output::github::render_payload(content));
+        tracing::info!("Dry run, gist left untouched");
+        return Ok(());
+    }
+
+    let (gist_id, token) = config.gist()?;
+    output::github::upload_gist(client, gist_id, token, content).await?;
     tracing::info!("Updated gist successfully");
+    Ok(())
+}
+
+async fn run_sync(
+    client: &reqwest::Client,
+    config: &config::Config,
+    dry_run: bool,
+) -> Result<(), errors::Error> {
+    let sync_config = config
+        .sync
+        .as_ref()
+        .ok_or_else(|| errors::Error::InvalidConfig {
+            field: "sync.ledger".to_string(),
+            reason: "missing required option".to_string(),
+        })?;
 
+    let window = providers::source::TimeWindow::last_days(config.days);
+    let plays =
+        providers::youtube::fetch_scrobbles(client, &sync_config.youtube_cookie, window).await?;
+    let plan = sync::plan(client, sync_config, plays, window).await?;
+    tracing::info!(
+        "{} plays to scrobble, {} already on Last.fm, {} already sent",
+        plan.pending.len(),
+        plan.on_lastfm,
+        plan.in_ledger
+    );
+
+    if dry_run {
+        for play in &plan.pending {
+            println!(
+                "{}\t{}\t{}",
+                play.played_at.to_rfc3339(),
+                play.track.artist,
+                play.track.title
+            );
+        }
+        tracing::info!("Dry run, nothing scrobbled");
+        return Ok(());
+    }
+
+    let submitted = sync::submit(client, sync_config, &plan.pending, chrono::Utc::now()).await?;
+    tracing::info!(
+        "Scrobbled {} plays, {} ignored by Last.fm",
+        submitted.accepted,
+        submitted.ignored
+    );
+    Ok(())
+}
+
+/// Runs Last.fm's desktop auth flow and saves the session key it ends with.
+async fn auth_lastfm(
+    client: &reqwest::Client,
+    overrides: &config::Overrides,
+) -> Result<(), errors::Error> {
+    let settings = config::Settings::load(overrides)?;
+    let app = lastfm_signed::App {
+        api_key: settings.require("lastfm.api_key")?,
+        api_secret: settings.require("lastfm.api_secret")?,
+        api_root: settings
+            .string("lastfm.api_root")?
+            .unwrap_or_else(|| providers::lastfm::DEFAULT_API_ROOT.to_string()),
+    };
+
+    let token = lastfm_signed::get_token(client, &app).await?;
+    println!("Open this page and allow access:");
+    println!("{}", lastfm_signed::auth_url(&app, &token));
+    tracing::info!("Waiting for approval");
+    let session_key =
+        lastfm_signed::wait_for_session(client, &app, &token, AUTH_POLL_INTERVAL).await?;
+
+    let path = config::store(overrides, "lastfm.session_key", &session_key)?;
+    println!("Saved lastfm.session_key to {}", path.display());
     Ok(())
 }
 
@@ -43,27 +207,75 @@ async fn fetch_scrobbles(
     client: &reqwest::Client,
     config: &config::Config,
 ) -> Result<Vec<providers::types::Scrobble>, errors::Error> {
-    let mut all_scrobbles = Vec::new();
-
-    if let Some(lastfm) = config.provider.lastfm() {
-        let scrobbles = providers::lastfm::fetch_scrobbles(
-            client,
-            &lastfm.api_key,
-            &lastfm.username,
-            config.days,
-        )
-        .await?;
-        tracing::info!("Last.fm: {} scrobbles", scrobbles.len());
-        all_scrobbles.extend(scrobbles);
+    let window = providers::source::TimeWindow::last_days(config.days);
+    config.sources.fetch_all(client, window).await
+}
+
+/// Prints a line per check and returns whether they all passed; the failures
+/// are already printed, so they are not returned as errors too.
+async fn doctor(client: &reqwest::Client, overrides: &config::Overrides) -> bool {
+    let config = match config::load_with(overrides) {
+        Ok(config) => config,
+        Err(error) => {
+            println!("✗ configuration: {}", error);
+            return false;
+        }
+    };
+    println!(
+        "✓ configuration: {} days, top {}",
+        config.days, config.top_n
+    );
+
+    for source in config.sources.sources() {
+        let capabilities = source.capabilities();
+        let mut notes = Vec::new();
+        if !capabilities.exact_timestamps {
+            notes.push("approximate timestamps");
+        }
+        if !capabilities.full_history {
+            notes.push("recent history only");
+        }
+
+        if notes.is_empty() {
+            println!("✓ source: {}", source.describe());
+        } else {
+            println!("✓ source: {} ({})", source.describe(), notes.join(", "));
+        }
     }
 
-    if let Some(cookie) = config.provider.youtube_cookie() {
-        let scrobbles = providers::youtube::fetch_scrobbles(client, cookie, config.days).await?;
-        tracing::info!("YouTube: {} scrobbles", scrobbles.len());
-        all_scrobbles.extend(scrobbles);
+    let result = match config.gist() {
+        Ok((gist_id, token)) => output::github::check_gist(client, gist_id, token).await,
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(()) => {
+            println!("✓ gist: reachable");
+            true
+        }
+        Err(error) => {
+            println!("✗ gist: {}", error);
+            false
+        }
     }
+}
+
+fn read_input(input: Option<std::path::PathBuf>) -> Result<String, errors::Error> {
+    let result = match &input {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map(|_| content)
+        }
+    };
 
-    Ok(all_scrobbles)
+    result.map_err(|e| errors::Error::Input {
+        path: input
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "stdin".to_string()),
+        detail: e.to_string(),
+    })
 }
 
 fn build_http_client() -> reqwest::Client {
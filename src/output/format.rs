@@ -5,17 +5,63 @@ const MAX_TITLE_WIDTH: usize = 35;
 const MAX_ARTIST_WIDTH: usize = 25;
 const ALIGN_POSITION: usize = 40;
 
+/// Lists the top tracks, then the top artists and albums when there are any.
+/// Once there is more than the tracks, each block gets a heading and a blank
+/// line before the next.
 pub fn format_statistics(stats: &Statistics) -> String {
-    if stats.top_tracks.is_empty() {
-        return "No tracks played recently".to_string();
-    }
-
-    stats
+    let tracks = stats
         .top_tracks
         .iter()
-        .map(|(track, count)| format_track_line(&track.title, &track.artist, *count))
-        .collect::<Vec<_>>()
-        .join("\n")
+        .map(|(track, count)| format_track_line(&track.title, &track.artist, *count));
+    let artists = stats
+        .top_artists
+        .iter()
+        .map(|(artist, count)| format_artist_line(artist, *count));
+    let albums = stats
+        .top_albums
+        .iter()
+        .map(|(album, count)| format_track_line(&album.title, &album.artist, *count));
+
+    let labelled = !stats.top_artists.is_empty() || !stats.top_albums.is_empty();
+    let blocks: Vec<String> = [
+        ("Top tracks", tracks.collect::<Vec<_>>()),
+        ("Top artists", artists.collect()),
+        ("Top albums", albums.collect()),
+    ]
+    .into_iter()
+    .filter(|(_, lines)| !lines.is_empty())
+    .map(|(heading, lines)| {
+        let lines = lines.join("\n");
+        if labelled {
+            format!("{}\n{}", heading, lines)
+        } else {
+            lines
+        }
+    })
+    .collect();
+
+    let top_list = if blocks.is_empty() {
+        "No tracks played recently".to_string()
+    } else {
+        blocks.join("\n\n")
+    };
+
+    match &stats.now_playing {
+        Some(track) => format!(
+            "{}\n{}",
+            format_now_playing(&track.title, &track.artist),
+            top_list
+        ),
+        None => top_list,
+    }
+}
+
+fn format_now_playing(title: &str, artist: &str) -> String {
+    format!(
+        "▶ Now playing: {} - {}",
+        truncate_with_ellipsis(title, MAX_TITLE_WIDTH),
+        truncate_with_ellipsis(artist, MAX_ARTIST_WIDTH)
+    )
 }
 
 fn format_track_line(title: &str, artist: &str, count: usize) -> String {
@@ -30,6 +76,24 @@ fn format_track_line(title: &str, artist: &str, count: usize) -> String {
     )
 }
 
+/// The artist takes the title column and the count the artist column, so
+/// the block lines up with the tracks and albums.
+fn format_artist_line(artist: &str, count: usize) -> String {
+    let truncated_artist = truncate_with_ellipsis(artist, MAX_TITLE_WIDTH);
+    let count_suffix = format_play_count(count);
+    if count_suffix.is_empty() {
+        return truncated_artist;
+    }
+
+    let padding = calculate_padding(&truncated_artist);
+    format!(
+        "{}{}{}",
+        truncated_artist,
+        padding,
+        count_suffix.trim_start()
+    )
+}
+
 fn truncate_with_ellipsis(text: &str, max_width: usize) -> String {
     if UnicodeWidthStr::width(text) <= max_width {
         return text.to_string();
@@ -40,6 +40,39 @@ pub async fn upload_gist(
     Ok(())
 }
 
+pub async fn check_gist(client: &reqwest::Client, gist_id: &str, token: &str) -> Result<(), Error> {
+    let url = format!("https://api.github.com/gists/{}", gist_id);
+
+    let response = client
+        .get(&url)
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28")
+        .send()
+        .await
+        .map_err(|e| Error::Network {
+            url: url.clone(),
+            source: e,
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::Gist {
+            gist_id: gist_id.to_string(),
+            status,
+            body,
+        });
+    }
+
+    Ok(())
+}
+
+/// Renders the JSON body that `upload_gist` sends, for previews.
+pub fn render_payload(content: &str) -> String {
+    serde_json::to_string_pretty(&build_payload(content)).expect("Gist payload is serializable")
+}
+
 fn build_payload(content: &str) -> GistUpdate {
     let mut files = HashMap::new();
     files.insert(
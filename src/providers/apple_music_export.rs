@@ -0,0 +1,158 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::files;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Duration, Utc};
+use std::path::PathBuf;
+
+const FILE_NAME: &str = "Apple Music Play Activity.csv";
+const NATURAL_END: &str = "NATURAL_END_OF_TRACK";
+
+#[derive(Debug, Clone)]
+pub struct AppleMusicExportConfig {
+    /// The CSV, a folder holding it, or a zip it is in.
+    pub paths: Vec<PathBuf>,
+    /// Plays that were cut short still count once they last this long.
+    pub min_played_ms: u64,
+}
+
+pub struct AppleMusicExportSource {
+    config: AppleMusicExportConfig,
+}
+
+/// Reads `apple_music_export.paths` and the optional
+/// `apple_music_export.min_played_seconds`, 30 by default.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let paths = settings.paths("apple_music_export.paths")?;
+    let min_played_seconds: u64 = settings
+        .parse("apple_music_export.min_played_seconds")?
+        .unwrap_or(30);
+
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![Box::new(AppleMusicExportSource::new(
+        AppleMusicExportConfig {
+            paths,
+            min_played_ms: min_played_seconds.saturating_mul(1000),
+        },
+    ))])
+}
+
+impl AppleMusicExportSource {
+    pub fn new(config: AppleMusicExportConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for AppleMusicExportSource {
+    fn name(&self) -> &str {
+        "Apple Music export"
+    }
+
+    fn describe(&self) -> String {
+        format!("Apple Music export ({} paths)", self.config.paths.len())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            let scrobbles = read_activity(&self.config)?;
+            Ok(scrobbles
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+pub fn read_activity(config: &AppleMusicExportConfig) -> Result<Vec<Scrobble>, Error> {
+    let mut scrobbles = Vec::new();
+    files::read_all(
+        &config.paths,
+        |path| files::file_name(path) == FILE_NAME,
+        |path, content| {
+            let parsed = parse_activity(content, config.min_played_ms)
+                .map_err(|e| files::input_error(path, e))?;
+            scrobbles.extend(parsed);
+            Ok(())
+        },
+    )?;
+    Ok(scrobbles)
+}
+
+/// Parses `Apple Music Play Activity.csv`. Only audio `PLAY_END` events are
+/// read; a play counts when the track ended on its own or lasted at least
+/// `min_played_ms`.
+///
+/// Older exports name the artist in `Artist Name`, newer ones in
+/// `Container Artist Name`; rows without either are skipped, as are rows
+/// without a song name or start time.
+pub fn parse_activity(content: &str, min_played_ms: u64) -> Result<Vec<Scrobble>, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+
+    let song = column("Song Name").or_else(|| column("Content Name"));
+    let artist = column("Artist Name").or_else(|| column("Container Artist Name"));
+    let album = column("Album Name").or_else(|| column("Container Album Name"));
+    let start = column("Event Start Timestamp");
+    let end = column("Event End Timestamp");
+    let event_type = column("Event Type");
+    let media_type = column("Media Type");
+    let end_reason = column("End Reason Type");
+    let played_ms = column("Play Duration Milliseconds");
+
+    let mut scrobbles = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let cell = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        if cell(event_type).is_some_and(|kind| kind != "PLAY_END")
+            || cell(media_type).is_some_and(|kind| kind != "AUDIO")
+        {
+            continue;
+        }
+
+        let played = cell(played_ms).and_then(|ms| ms.parse::<i64>().ok());
+        let natural_end = cell(end_reason) == Some(NATURAL_END);
+        if !natural_end && played.is_none_or(|ms| ms < min_played_ms as i64) {
+            continue;
+        }
+
+        let timestamp = |value: Option<&str>| value?.parse::<DateTime<Utc>>().ok();
+        let played_at = timestamp(cell(start)).or_else(|| {
+            timestamp(cell(end)).map(|ended| ended - Duration::milliseconds(played.unwrap_or(0)))
+        });
+
+        let (Some(title), Some(artist), Some(played_at)) = (cell(song), cell(artist), played_at)
+        else {
+            continue;
+        };
+        scrobbles.push(
+            Scrobble::new(artist.to_string(), title.to_string(), played_at)
+                .with_album(cell(album).map(String::from)),
+        );
+    }
+
+    Ok(scrobbles)
+}
@@ -0,0 +1,181 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::history;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::{ChildStderr, Command};
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 60;
+/// How long a timed out command's stderr is still read after killing it.
+const STDERR_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct CommandConfig {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+    /// The command is killed if it has not finished by then.
+    pub timeout: Duration,
+}
+
+pub struct CommandSource {
+    config: CommandConfig,
+}
+
+/// Reads every `[command.<name>]` section of the config file.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let mut sources: Vec<Box<dyn ScrobbleSource>> = Vec::new();
+
+    for name in settings.sections("command") {
+        let key = |option: &str| format!("command.{}.{}", name, option);
+
+        let timeout_seconds: u64 = settings
+            .parse(&key("timeout_seconds"))?
+            .unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        if timeout_seconds == 0 {
+            return Err(settings.invalid(&key("timeout_seconds"), "must be greater than 0"));
+        }
+
+        sources.push(Box::new(CommandSource::new(CommandConfig {
+            program: settings.require(&key("program"))?,
+            args: settings.strings(&key("args"))?,
+            timeout: Duration::from_secs(timeout_seconds),
+            name: name.clone(),
+        })));
+    }
+
+    Ok(sources)
+}
+
+impl CommandSource {
+    pub fn new(config: CommandConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for CommandSource {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn describe(&self) -> String {
+        format!("{} (runs {})", self.config.name, self.config.program)
+    }
+
+    /// Nothing is known about what the command reads from.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            Ok(run(&self.config, window)
+                .await?
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+/// Runs the command and reads one scrobble per line of its output, in the
+/// same shape as the history files. The window is passed in
+/// `MUSIC_STATS_FROM` and `MUSIC_STATS_TO` as RFC 3339 timestamps so the
+/// command can skip older plays, though anything outside it is dropped anyway.
+pub async fn run(config: &CommandConfig, window: TimeWindow) -> Result<Vec<Scrobble>, Error> {
+    let failed = |detail: String| Error::Command {
+        name: config.name.clone(),
+        detail,
+    };
+
+    let mut child = Command::new(&config.program)
+        .args(&config.args)
+        .env("MUSIC_STATS_FROM", window.from.to_rfc3339())
+        .env("MUSIC_STATS_TO", window.to.to_rfc3339())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| failed(format!("cannot start {}: {}", config.program, e)))?;
+
+    let mut stdout_pipe = child.stdout.take().expect("stdout is piped");
+    let mut stdout_task = tokio::spawn(async move {
+        let mut stdout = Vec::new();
+        stdout_pipe.read_to_end(&mut stdout).await.map(|_| stdout)
+    });
+    // Filled as the command writes, so a timeout can still report it.
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    let mut stderr_task = tokio::spawn(collect(
+        child.stderr.take().expect("stderr is piped"),
+        stderr.clone(),
+    ));
+
+    let finished = tokio::time::timeout(config.timeout, async {
+        let status = child.wait().await.map_err(|e| e.to_string())?;
+        let stdout = (&mut stdout_task).await.map_err(|e| e.to_string())?;
+        let read_stderr = (&mut stderr_task).await.map_err(|e| e.to_string())?;
+        read_stderr.map_err(|e| e.to_string())?;
+        stdout
+            .map(|stdout| (status, stdout))
+            .map_err(|e| e.to_string())
+    })
+    .await;
+
+    let (status, stdout) = match finished {
+        Ok(output) => output.map_err(failed)?,
+        Err(_) => {
+            let _ = child.kill().await;
+            // The pipe closes once the command is gone, unless something it
+            // started still holds it open.
+            let _ = tokio::time::timeout(STDERR_GRACE, stderr_task).await;
+            let stderr = String::from_utf8_lossy(&stderr.lock().unwrap()).into_owned();
+            return Err(failed(format!(
+                "timed out after {}s - stderr: {}",
+                config.timeout.as_secs(),
+                stderr.trim()
+            )));
+        }
+    };
+    let stderr = String::from_utf8_lossy(&stderr.lock().unwrap()).into_owned();
+    if !status.success() {
+        return Err(failed(format!("{} - stderr: {}", status, stderr.trim())));
+    }
+
+    let stdout =
+        String::from_utf8(stdout).map_err(|_| failed("output is not valid UTF-8".to_string()))?;
+    stdout
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            history::parse_line(line).map_err(|e| {
+                failed(format!(
+                    "line {}: {} - stderr: {}",
+                    index + 1,
+                    e,
+                    stderr.trim()
+                ))
+            })
+        })
+        .collect()
+}
+
+async fn collect(mut pipe: ChildStderr, into: Arc<Mutex<Vec<u8>>>) -> std::io::Result<()> {
+    let mut buffer = [0; 4096];
+    loop {
+        let read = pipe.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        into.lock().unwrap().extend_from_slice(&buffer[..read]);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const DEFAULT_API_ROOT: &str = "https://api.deezer.com";
+const PAGE_LIMIT: usize = 100;
+const MAX_PAGES: usize = 50;
+const RATE_LIMIT_MS: u64 = 200;
+
+#[derive(Debug, Clone)]
+pub struct DeezerConfig {
+    /// OAuth access token with the `listening_history` permission.
+    pub access_token: String,
+    pub api_root: String,
+}
+
+pub struct DeezerSource {
+    config: DeezerConfig,
+}
+
+/// Reads `deezer.access_token`, plus `deezer.api_root` to point at another
+/// server.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let access_token = settings.string("deezer.access_token")?;
+    let api_root = settings.string("deezer.api_root")?;
+
+    match access_token {
+        Some(access_token) => Ok(vec![Box::new(DeezerSource::new(DeezerConfig {
+            access_token,
+            api_root: api_root.unwrap_or_else(|| DEFAULT_API_ROOT.to_string()),
+        }))]),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl DeezerSource {
+    pub fn new(config: DeezerConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for DeezerSource {
+    fn name(&self) -> &str {
+        "Deezer"
+    }
+
+    /// Deezer only keeps the most recent listens.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: false,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(fetch_scrobbles(client, &self.config, window))
+    }
+}
+
+/// Follows `next` from the newest listens back until a page reaches past the
+/// start of the window.
+pub async fn fetch_scrobbles(
+    client: &reqwest::Client,
+    config: &DeezerConfig,
+    window: TimeWindow,
+) -> Result<Vec<Scrobble>, Error> {
+    let mut url = reqwest::Url::parse_with_params(
+        &format!("{}/user/me/history", config.api_root.trim_end_matches('/')),
+        &[
+            ("access_token", config.access_token.as_str()),
+            ("limit", &PAGE_LIMIT.to_string()),
+        ],
+    )
+    .map_err(|e| Error::InvalidConfig {
+        field: "deezer.api_root".to_string(),
+        reason: e.to_string(),
+    })?
+    .to_string();
+    let mut scrobbles = Vec::new();
+
+    for page in 1..=MAX_PAGES {
+        let response = fetch_page(client, &url).await?;
+        let oldest = response.data.iter().map(|entry| entry.timestamp).min();
+
+        scrobbles.extend(
+            response
+                .data
+                .into_iter()
+                .filter_map(parse_entry)
+                .filter(|s| window.contains(s.played_at)),
+        );
+
+        match (response.next, oldest) {
+            (Some(next), Some(oldest)) if oldest >= window.from.timestamp() => {
+                if page == MAX_PAGES {
+                    tracing::warn!(
+                        "Stopped after {} pages of Deezer history; plays before {} are not counted",
+                        MAX_PAGES,
+                        Utc.timestamp_opt(oldest, 0).unwrap()
+                    );
+                    break;
+                }
+                tracing::info!("Fetching Deezer history page {}", page + 1);
+                url = next;
+                sleep(Duration::from_millis(RATE_LIMIT_MS)).await;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(scrobbles)
+}
+
+async fn fetch_page(client: &reqwest::Client, url: &str) -> Result<ApiResponse, Error> {
+    // The access token travels in the query, so errors only name the endpoint.
+    let endpoint = url.split('?').next().unwrap_or(url).to_string();
+
+    let response = client.get(url).send().await.map_err(|e| Error::Network {
+        url: endpoint.clone(),
+        source: e.without_url(),
+    })?;
+
+    let status = response.status().as_u16();
+    let body = response.text().await.map_err(|e| Error::Network {
+        url: endpoint.clone(),
+        source: e.without_url(),
+    })?;
+
+    // Deezer reports most failures, expired tokens included, as a 200 whose
+    // body holds an `error` object.
+    match serde_json::from_str::<ApiResponse>(&body) {
+        Ok(page) if (200..300).contains(&status) && page.error.is_none() => Ok(page),
+        _ => Err(Error::Deezer {
+            status,
+            url: endpoint,
+            body,
+        }),
+    }
+}
+
+fn parse_entry(entry: ApiEntry) -> Option<Scrobble> {
+    let played_at = Utc.timestamp_opt(entry.timestamp, 0).single()?;
+    Some(
+        Scrobble::new(entry.artist.name, entry.title, played_at)
+            .with_album(entry.album.map(|album| album.title)),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    #[serde(default)]
+    data: Vec<ApiEntry>,
+    next: Option<String>,
+    error: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiEntry {
+    title: String,
+    timestamp: i64,
+    artist: ApiArtist,
+    album: Option<ApiAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiArtist {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAlbum {
+    title: String,
+}
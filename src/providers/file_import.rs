@@ -0,0 +1,373 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::files;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampFormat {
+    Rfc3339,
+    /// Seconds since the epoch.
+    Unix,
+    /// Milliseconds since the epoch.
+    UnixMillis,
+    /// A `chrono` format string such as `%Y-%m-%d %H:%M:%S`.
+    Pattern(String),
+}
+
+/// Where each field is found. For CSV these are header names, or zero-based
+/// column numbers when the file has no header; for JSON Lines they are field
+/// names, with dots to reach nested fields (`track.artist.name`).
+#[derive(Debug, Clone)]
+pub struct FieldMapping {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub timestamp: String,
+    pub timestamp_format: TimestampFormat,
+    /// Zone for timestamps that do not carry an offset.
+    pub timezone: Tz,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileImportConfig {
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+    /// Guessed from each file's extension when not set.
+    pub format: Option<FileFormat>,
+    pub has_header: bool,
+    /// A tab for `.tsv` files and a comma for the rest when not set.
+    pub delimiter: Option<u8>,
+    pub mapping: FieldMapping,
+}
+
+/// Scrobbles read from a file, and how many rows were left out for each reason.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub scrobbles: Vec<Scrobble>,
+    pub skipped: BTreeMap<&'static str, usize>,
+}
+
+pub struct FileImportSource {
+    config: FileImportConfig,
+}
+
+/// Reads every `[file_import.<name>]` section of the config file.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let mut sources: Vec<Box<dyn ScrobbleSource>> = Vec::new();
+
+    for name in settings.sections("file_import") {
+        let key = |option: &str| format!("file_import.{}.{}", name, option);
+
+        let format = match settings.string(&key("format"))?.as_deref() {
+            None => None,
+            Some("csv") => Some(FileFormat::Csv),
+            Some("jsonl") => Some(FileFormat::JsonLines),
+            Some(_) => return Err(settings.invalid(&key("format"), "expected csv or jsonl")),
+        };
+        let delimiter = match settings.string(&key("delimiter"))? {
+            None => None,
+            Some(d) if d.len() == 1 => Some(d.as_bytes()[0]),
+            Some(_) => {
+                return Err(settings.invalid(&key("delimiter"), "expected a single character"));
+            }
+        };
+        let timestamp_format = match settings.string(&key("timestamp_format"))?.as_deref() {
+            None | Some("rfc3339") => TimestampFormat::Rfc3339,
+            Some("unix") => TimestampFormat::Unix,
+            Some("unix_ms") => TimestampFormat::UnixMillis,
+            Some(pattern) => TimestampFormat::Pattern(pattern.to_string()),
+        };
+        let timezone = match settings.string(&key("timezone"))? {
+            None => Tz::UTC,
+            Some(zone) => zone
+                .parse()
+                .map_err(|_| settings.invalid(&key("timezone"), "unknown time zone"))?,
+        };
+
+        let config = FileImportConfig {
+            paths: settings.paths(&key("paths"))?,
+            format,
+            has_header: settings.parse(&key("has_header"))?.unwrap_or(true),
+            delimiter,
+            mapping: FieldMapping {
+                artist: settings.require(&key("artist"))?,
+                title: settings.require(&key("title"))?,
+                album: settings.string(&key("album"))?,
+                timestamp: settings.require(&key("timestamp"))?,
+                timestamp_format,
+                timezone,
+            },
+            name: name.clone(),
+        };
+
+        if config.paths.is_empty() {
+            return Err(settings.invalid(&key("paths"), "missing required option"));
+        }
+
+        sources.push(Box::new(FileImportSource::new(config)));
+    }
+
+    Ok(sources)
+}
+
+impl FileImportSource {
+    pub fn new(config: FileImportConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for FileImportSource {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn describe(&self) -> String {
+        format!("{} (file import)", self.config.name)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            let report = read_files(&self.config)?;
+            if !report.skipped.is_empty() {
+                tracing::warn!(
+                    "{}: skipped {} rows ({})",
+                    self.config.name,
+                    report.skipped_total(),
+                    report.describe_skipped()
+                );
+            }
+
+            Ok(report
+                .scrobbles
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+impl ImportReport {
+    pub fn skipped_total(&self) -> usize {
+        self.skipped.values().sum()
+    }
+
+    /// Lists the skip reasons, e.g. "2 missing artist, 1 bad timestamp".
+    pub fn describe_skipped(&self) -> String {
+        self.skipped
+            .iter()
+            .map(|(reason, count)| format!("{} {}", count, reason))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn merge(&mut self, other: ImportReport) {
+        self.scrobbles.extend(other.scrobbles);
+        for (reason, count) in other.skipped {
+            *self.skipped.entry(reason).or_insert(0) += count;
+        }
+    }
+
+    fn push(&mut self, row: Result<Scrobble, &'static str>) {
+        match row {
+            Ok(scrobble) => self.scrobbles.push(scrobble),
+            Err(reason) => *self.skipped.entry(reason).or_insert(0) += 1,
+        }
+    }
+}
+
+pub fn read_files(config: &FileImportConfig) -> Result<ImportReport, Error> {
+    let mut report = ImportReport::default();
+
+    // Named paths are always read; inside folders and zips only files with a
+    // known extension are.
+    files::read_all(
+        &config.paths,
+        |path| format_of(Path::new(path)).is_some(),
+        |path, content| {
+            let format = config.format.or_else(|| format_of(path)).ok_or_else(|| {
+                files::input_error(path, "unknown format, set `format` to csv or jsonl")
+            })?;
+            let parsed = match format {
+                FileFormat::Csv => parse_csv(
+                    content,
+                    config.has_header,
+                    config.delimiter.unwrap_or_else(|| delimiter_of(path)),
+                    &config.mapping,
+                )
+                .map_err(|e| files::input_error(path, e))?,
+                FileFormat::JsonLines => parse_json_lines(content, &config.mapping),
+            };
+            report.merge(parsed);
+            Ok(())
+        },
+    )?;
+
+    Ok(report)
+}
+
+/// Parses delimited text. Fails when the file cannot be read as CSV or a
+/// mapped column does not exist; rows with missing or unusable values are
+/// counted in the report instead.
+pub fn parse_csv(
+    content: &str,
+    has_header: bool,
+    delimiter: u8,
+    mapping: &FieldMapping,
+) -> Result<ImportReport, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(has_header)
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers = if has_header {
+        Some(reader.headers().map_err(|e| e.to_string())?.clone())
+    } else {
+        None
+    };
+    let column = |name: &str| -> Result<usize, String> {
+        match &headers {
+            Some(headers) => headers
+                .iter()
+                .position(|header| header.trim() == name)
+                .ok_or_else(|| format!("column `{}` not found in header", name)),
+            None => name
+                .parse()
+                .map_err(|_| format!("column `{}` must be a number without a header", name)),
+        }
+    };
+
+    let artist = column(&mapping.artist)?;
+    let title = column(&mapping.title)?;
+    let timestamp = column(&mapping.timestamp)?;
+    let album = mapping.album.as_deref().map(column).transpose()?;
+
+    let mut report = ImportReport::default();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let cell = |index: usize| record.get(index).map(str::trim).filter(|v| !v.is_empty());
+
+        report.push(build_scrobble(
+            cell(artist),
+            cell(title),
+            album.and_then(cell),
+            cell(timestamp),
+            mapping,
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Parses one JSON object per line. Lines that are not valid JSON are
+/// skipped and counted like any other unusable row.
+pub fn parse_json_lines(content: &str, mapping: &FieldMapping) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            report.push(Err("invalid JSON"));
+            continue;
+        };
+
+        let field = |name: &str| -> Option<String> {
+            let pointer = format!("/{}", name.replace('.', "/"));
+            match value.pointer(&pointer)? {
+                Value::String(text) => Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+                Value::Number(number) => Some(number.to_string()),
+                _ => None,
+            }
+        };
+
+        report.push(build_scrobble(
+            field(&mapping.artist).as_deref(),
+            field(&mapping.title).as_deref(),
+            mapping.album.as_deref().and_then(field).as_deref(),
+            field(&mapping.timestamp).as_deref(),
+            mapping,
+        ));
+    }
+
+    report
+}
+
+fn build_scrobble(
+    artist: Option<&str>,
+    title: Option<&str>,
+    album: Option<&str>,
+    timestamp: Option<&str>,
+    mapping: &FieldMapping,
+) -> Result<Scrobble, &'static str> {
+    let artist = artist.ok_or("missing artist")?;
+    let title = title.ok_or("missing title")?;
+    let timestamp = timestamp.ok_or("missing timestamp")?;
+    let played_at = parse_timestamp(timestamp, mapping).ok_or("bad timestamp")?;
+
+    Ok(
+        Scrobble::new(artist.to_string(), title.to_string(), played_at)
+            .with_album(album.map(String::from)),
+    )
+}
+
+fn parse_timestamp(value: &str, mapping: &FieldMapping) -> Option<DateTime<Utc>> {
+    match &mapping.timestamp_format {
+        TimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+        TimestampFormat::Unix => Utc.timestamp_opt(value.parse().ok()?, 0).single(),
+        TimestampFormat::UnixMillis => Utc.timestamp_millis_opt(value.parse().ok()?).single(),
+        TimestampFormat::Pattern(pattern) => {
+            if let Ok(time) = DateTime::parse_from_str(value, pattern) {
+                return Some(time.with_timezone(&Utc));
+            }
+
+            let naive = NaiveDateTime::parse_from_str(value, pattern)
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(value, pattern)
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+                })
+                .ok()?;
+            let local = mapping.timezone.from_local_datetime(&naive).earliest()?;
+            Some(local.with_timezone(&Utc))
+        }
+    }
+}
+
+fn delimiter_of(path: &Path) -> u8 {
+    let is_tsv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"));
+    if is_tsv { b'\t' } else { b',' }
+}
+
+fn format_of(path: &Path) -> Option<FileFormat> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "csv" | "tsv" => Some(FileFormat::Csv),
+        "jsonl" | "ndjson" => Some(FileFormat::JsonLines),
+        _ => None,
+    }
+}
@@ -0,0 +1,90 @@
+use crate::errors::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Calls `read` with the path and content of every file found under `paths`.
+///
+/// Files named directly are always read. Folders and zip archives are searched
+/// one level deep for entries whose name `wanted` accepts, which lets users
+/// point at an export as they downloaded it.
+pub fn read_all(
+    paths: &[PathBuf],
+    wanted: impl Fn(&str) -> bool,
+    mut read: impl FnMut(&Path, &str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    for path in paths {
+        if path.is_dir() {
+            read_dir(path, &wanted, &mut read)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+        {
+            read_zip(path, &wanted, &mut read)?;
+        } else {
+            let content = std::fs::read_to_string(path).map_err(|e| input_error(path, e))?;
+            read(path, &content)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn input_error(path: &Path, error: impl std::fmt::Display) -> Error {
+    Error::Input {
+        path: path.display().to_string(),
+        detail: error.to_string(),
+    }
+}
+
+/// Returns the last component of a path inside a zip or on disk.
+pub fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn read_dir(
+    path: &Path,
+    wanted: &impl Fn(&str) -> bool,
+    read: &mut impl FnMut(&Path, &str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(path).map_err(|e| input_error(path, e))? {
+        let entry_path = entry.map_err(|e| input_error(path, e))?.path();
+        if entry_path.is_file() && wanted(&entry_path.to_string_lossy()) {
+            entries.push(entry_path);
+        }
+    }
+    entries.sort();
+
+    for entry_path in entries {
+        let content =
+            std::fs::read_to_string(&entry_path).map_err(|e| input_error(&entry_path, e))?;
+        read(&entry_path, &content)?;
+    }
+
+    Ok(())
+}
+
+fn read_zip(
+    path: &Path,
+    wanted: &impl Fn(&str) -> bool,
+    read: &mut impl FnMut(&Path, &str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let file = std::fs::File::open(path).map_err(|e| input_error(path, e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| input_error(path, e))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|e| input_error(path, e))?;
+        if !entry.is_file() || !wanted(entry.name()) {
+            continue;
+        }
+
+        let entry_path = path.join(entry.name());
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .map_err(|e| input_error(&entry_path, e))?;
+        read(&entry_path, &content)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use crate::errors::Error;
+use crate::providers::files;
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// One line of a history file. Listeners that record plays as they happen
+/// (MPD, media server webhooks) append these, and their sources read them back.
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    played_at: DateTime<Utc>,
+    artist: String,
+    title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    album: Option<String>,
+}
+
+/// Reads the plays recorded so far. A missing file just means nothing has
+/// been recorded yet; lines that cannot be read are skipped with a warning.
+pub fn read(path: &Path) -> Result<Vec<Scrobble>, Error> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(files::input_error(path, e)),
+    };
+
+    let mut scrobbles = Vec::new();
+    let mut skipped = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_line(line) {
+            Ok(scrobble) => scrobbles.push(scrobble),
+            Err(_) => skipped += 1,
+        }
+    }
+
+    if skipped > 0 {
+        tracing::warn!("Skipped {} unreadable lines in {}", skipped, path.display());
+    }
+
+    Ok(scrobbles)
+}
+
+/// Reads one line: an object with `artist`, `title`, an RFC 3339 `played_at`
+/// and an optional `album`.
+pub fn parse_line(line: &str) -> Result<Scrobble, serde_json::Error> {
+    let entry: HistoryEntry = serde_json::from_str(line)?;
+    Ok(Scrobble::new(entry.artist, entry.title, entry.played_at).with_album(entry.album))
+}
+
+/// Adds one play to the end of the file, creating it if needed.
+pub fn append(path: &Path, scrobble: &Scrobble) -> std::io::Result<()> {
+    let entry = HistoryEntry {
+        played_at: scrobble.played_at,
+        artist: scrobble.track.artist.clone(),
+        title: scrobble.track.title.clone(),
+        album: scrobble.album.clone(),
+    };
+    let mut line = serde_json::to_string(&entry).expect("history entries always serialize");
+    line.push('\n');
+
+    // One write on an append handle, so concurrent appends never interleave.
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(line.as_bytes())
+}
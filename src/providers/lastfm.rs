@@ -1,100 +1,506 @@
-use crate::errors::Error;
-use crate::providers::types::Scrobble;
+use crate::config::Settings;
+use crate::errors::{Error, LastFmErrorCode};
+use crate::providers::lastfm_signed::{self, App, Session};
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::{Artwork, Metadata, Scrobble, Track};
 use chrono::{TimeZone, Utc};
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::task::JoinSet;
 use tokio::time::sleep;
 
+pub const DEFAULT_API_ROOT: &str = "https://ws.audioscrobbler.com/2.0";
 const PAGE_LIMIT: usize = 200;
-const MAX_PAGES: usize = 10;
+/// Pages requested at the same time once the page count is known.
+const CONCURRENT_PAGES: usize = 4;
+/// Gap between starting requests, which keeps us under Last.fm's limit of
+/// five per second however many are in flight.
 const RATE_LIMIT_MS: u64 = 200;
+/// Rate limited or temporarily failing requests are sent again this many
+/// times, waiting twice as long each time.
+const MAX_RETRIES: u32 = 3;
+const RETRY_DELAY_MS: u64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct LastFmConfig {
+    /// Shown in logs and `doctor`, so several instances can be told apart.
+    pub name: String,
+    pub api_key: String,
+    pub username: String,
+    /// Any server speaking the Last.fm 2.0 API, such as `https://libre.fm/2.0`.
+    pub api_root: String,
+    /// Stop after this many pages of 200 scrobbles. Unlimited when unset.
+    pub max_pages: Option<usize>,
+    /// Shared secret of the API account, needed to sign calls.
+    pub api_secret: Option<String>,
+    /// From `music-stats auth lastfm`. With the secret, calls are made as the
+    /// user, which private profiles need.
+    pub session_key: Option<String>,
+}
+
+impl LastFmConfig {
+    /// The credentials for signed calls, when both the secret and a session
+    /// key are configured.
+    pub fn session(&self) -> Option<Session> {
+        Some(Session {
+            app: App {
+                api_key: self.api_key.clone(),
+                api_secret: self.api_secret.clone()?,
+                api_root: self.api_root.clone(),
+            },
+            session_key: self.session_key.clone()?,
+        })
+    }
+}
+
+pub struct LastFmSource {
+    config: LastFmConfig,
+    /// What the last `fetch` found playing, so asking after one costs no
+    /// request. `None` until a fetch has run, as in charts mode.
+    now_playing: Mutex<Option<Option<Track>>>,
+}
+
+/// Scrobbles in a window, and the track Last.fm listed as playing.
+#[derive(Debug, Default)]
+pub struct History {
+    pub scrobbles: Vec<Scrobble>,
+    pub now_playing: Option<Track>,
+}
+
+/// Reads `lastfm.api_key` and `lastfm.username`, both needed, plus one more
+/// source for each `[lastfm.<name>]` section, which is how Libre.fm and other
+/// GNU FM servers are added next to Last.fm. Those sections also need
+/// `api_root`, so another server's key and username never reach Last.fm by
+/// mistake. Each may set `max_pages`, and `api_secret` with `session_key` to
+/// make signed calls.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let mut sources: Vec<Box<dyn ScrobbleSource>> = Vec::new();
+
+    if let Some(config) = config(settings)? {
+        sources.push(Box::new(LastFmSource::new(config)));
+    }
+
+    for section in settings.sections("lastfm") {
+        let key = |option: &str| format!("lastfm.{}.{}", section, option);
+
+        sources.push(Box::new(LastFmSource::new(LastFmConfig {
+            api_key: settings.require(&key("api_key"))?,
+            username: settings.require(&key("username"))?,
+            api_root: settings.require(&key("api_root"))?,
+            max_pages: parse_max_pages(settings, &key("max_pages"))?,
+            api_secret: settings.string(&key("api_secret"))?,
+            session_key: settings.string(&key("session_key"))?,
+            name: settings.string(&key("name"))?.unwrap_or(section),
+        })));
+    }
+
+    Ok(sources)
+}
+
+/// The `[lastfm]` account itself, when its key and username are both set.
+pub fn config(settings: &Settings) -> Result<Option<LastFmConfig>, Error> {
+    let api_key = settings.string("lastfm.api_key")?;
+    let username = settings.string("lastfm.username")?;
+    let api_root = settings.string("lastfm.api_root")?;
+    let name = settings.string("lastfm.name")?;
+    let max_pages = parse_max_pages(settings, "lastfm.max_pages")?;
+    let api_secret = settings.string("lastfm.api_secret")?;
+    let session_key = settings.string("lastfm.session_key")?;
+
+    let (Some(api_key), Some(username)) = (api_key, username) else {
+        return Ok(None);
+    };
+    Ok(Some(LastFmConfig {
+        name: name.unwrap_or_else(|| "Last.fm".to_string()),
+        api_key,
+        username,
+        api_root: api_root.unwrap_or_else(|| DEFAULT_API_ROOT.to_string()),
+        max_pages,
+        api_secret,
+        session_key,
+    }))
+}
+
+fn parse_max_pages(settings: &Settings, key: &str) -> Result<Option<usize>, Error> {
+    match settings.parse(key)? {
+        Some(0) => Err(settings.invalid(key, "must be greater than 0")),
+        max_pages => Ok(max_pages),
+    }
+}
+
+impl LastFmSource {
+    pub fn new(config: LastFmConfig) -> Self {
+        Self {
+            config,
+            now_playing: Mutex::new(None),
+        }
+    }
+}
+
+impl ScrobbleSource for LastFmSource {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn describe(&self) -> String {
+        format!("{} ({})", self.config.name, self.config.username)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            let history = fetch_history(client, &self.config, window).await?;
+            *self.now_playing.lock().unwrap() = Some(history.now_playing);
+            Ok(history.scrobbles)
+        })
+    }
+
+    fn now_playing<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+    ) -> BoxFuture<'a, Result<Option<Track>, Error>> {
+        let fetched = self.now_playing.lock().unwrap().clone();
+        Box::pin(async move {
+            match fetched {
+                Some(track) => Ok(track),
+                None => fetch_now_playing(client, &self.config).await,
+            }
+        })
+    }
+}
 
 pub async fn fetch_scrobbles(
     client: &reqwest::Client,
-    api_key: &str,
-    username: &str,
-    days: u64,
+    config: &LastFmConfig,
+    window: TimeWindow,
 ) -> Result<Vec<Scrobble>, Error> {
-    let from_timestamp = calculate_timestamp(days);
-    let tracks = fetch_all_pages(client, api_key, username, from_timestamp).await?;
-    Ok(tracks.into_iter().filter_map(parse_track).collect())
+    Ok(fetch_history(client, config, window).await?.scrobbles)
 }
 
-async fn fetch_all_pages(
+/// Every page is bounded by the end of the window as well as its start, so
+/// plays scrobbled while we are paging cannot push tracks onto a later page.
+/// A play that still shows up twice is only kept once.
+///
+/// The track playing right now is listed first, ahead of the latest
+/// scrobble, and has no date.
+pub async fn fetch_history(
     client: &reqwest::Client,
-    api_key: &str,
-    username: &str,
-    from: u64,
-) -> Result<Vec<ApiTrack>, Error> {
-    let mut all_tracks = Vec::new();
-    let mut page = 1;
+    config: &LastFmConfig,
+    window: TimeWindow,
+) -> Result<History, Error> {
+    let range = PageRange {
+        from: window.from.timestamp().max(0) as u64,
+        to: window.to.timestamp().max(0) as u64,
+    };
+    let tracks = fetch_all_pages(client, config, range).await?;
 
-    loop {
-        let response = fetch_page(client, api_key, username, page, from).await?;
-        all_tracks.extend(response.recenttracks.track);
+    let now_playing = tracks
+        .iter()
+        .find(|track| track.is_now_playing())
+        .map(|track| Track {
+            artist: track.artist.name.clone(),
+            title: track.name.clone(),
+        });
+
+    let mut seen = HashSet::new();
+    let scrobbles = tracks
+        .into_iter()
+        .filter_map(parse_track)
+        .filter(|s| window.contains(s.played_at))
+        .filter(|s| seen.insert((s.track.clone(), s.played_at)))
+        .collect();
+
+    Ok(History {
+        scrobbles,
+        now_playing,
+    })
+}
+
+/// Unix timestamps every page of one run is limited to.
+#[derive(Debug, Clone, Copy)]
+struct PageRange {
+    from: u64,
+    to: u64,
+}
 
-        let total_pages = response
-            .recenttracks
-            .attr
-            .and_then(|a| a.total_pages.parse().ok())
-            .unwrap_or(1);
+/// Reads the first page to learn how many there are, then fetches the rest
+/// a few at a time and returns every track, newest first.
+async fn fetch_all_pages(
+    client: &reqwest::Client,
+    config: &LastFmConfig,
+    range: PageRange,
+) -> Result<Vec<ApiTrack>, Error> {
+    let first = fetch_page(client, config, 1, range).await?;
+    let total_pages = first
+        .recenttracks
+        .attr
+        .as_ref()
+        .and_then(|a| a.total_pages.parse().ok())
+        .unwrap_or(1);
 
-        if page >= total_pages || page >= MAX_PAGES {
-            break;
+    let last_page = match config.max_pages {
+        Some(max_pages) if max_pages < total_pages => {
+            tracing::warn!(
+                "{} has {} pages of scrobbles but max_pages is {}; only the most recent {} are counted",
+                config.name,
+                total_pages,
+                max_pages,
+                max_pages * PAGE_LIMIT
+            );
+            max_pages
         }
+        _ => total_pages,
+    };
+
+    let mut pages = BTreeMap::new();
+    pages.insert(1, first.recenttracks.track);
 
-        if total_pages > 1 {
-            tracing::info!("Fetching Last.fm page {}/{}", page + 1, total_pages);
+    let mut pending = JoinSet::new();
+    let mut next_page = 2;
+    while next_page <= last_page || !pending.is_empty() {
+        if next_page <= last_page && pending.len() < CONCURRENT_PAGES {
+            let client = client.clone();
+            let config = config.clone();
+            let page = next_page;
+            pending.spawn(async move { (page, fetch_page(&client, &config, page, range).await) });
+            next_page += 1;
+            sleep(Duration::from_millis(RATE_LIMIT_MS)).await;
+            continue;
         }
 
-        page += 1;
-        sleep(Duration::from_millis(RATE_LIMIT_MS)).await;
+        let (page, response) = match pending.join_next().await {
+            Some(Ok(result)) => result,
+            Some(Err(error)) => std::panic::resume_unwind(error.into_panic()),
+            None => break,
+        };
+        // Dropping `pending` on an error cancels the requests still running.
+        pages.insert(page, response?.recenttracks.track);
+        tracing::info!(
+            "Fetched {} of {} {} pages",
+            pages.len(),
+            last_page,
+            config.name
+        );
     }
 
-    Ok(all_tracks)
+    Ok(pages.into_values().flatten().collect())
+}
+
+/// Returns the track Last.fm marks as now playing, if any, with a request of
+/// its own.
+pub async fn fetch_now_playing(
+    client: &reqwest::Client,
+    config: &LastFmConfig,
+) -> Result<Option<Track>, Error> {
+    let response = fetch_recent_tracks(client, config, "limit=1").await?;
+    Ok(response
+        .recenttracks
+        .track
+        .into_iter()
+        .find(|track| track.is_now_playing())
+        .map(|track| Track {
+            artist: track.artist.name,
+            title: track.name,
+        }))
 }
 
 async fn fetch_page(
     client: &reqwest::Client,
-    api_key: &str,
-    username: &str,
+    config: &LastFmConfig,
     page: usize,
-    from: u64,
+    range: PageRange,
 ) -> Result<ApiResponse, Error> {
+    let query = format!(
+        "limit={}&from={}&to={}&page={}",
+        PAGE_LIMIT, range.from, range.to, page
+    );
+    fetch_recent_tracks(client, config, &query).await
+}
+
+/// Calls `user.getrecenttracks` with the extra `query` parameters.
+async fn fetch_recent_tracks(
+    client: &reqwest::Client,
+    config: &LastFmConfig,
+    query: &str,
+) -> Result<ApiResponse, Error> {
+    let query = format!("extended=1&{}", query);
+    call_user_method(client, config, "user.getrecenttracks", &query).await
+}
+
+/// Calls a `user.*` method for the configured user with the extra `query`
+/// parameters, retrying rate limits and temporary failures.
+pub async fn call_user_method<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    config: &LastFmConfig,
+    method: &str,
+    query: &str,
+) -> Result<T, Error> {
+    let mut delay = Duration::from_millis(RETRY_DELAY_MS);
+    let mut attempt = 0;
+
+    loop {
+        match request(client, config, method, query).await {
+            Err(error) if attempt < MAX_RETRIES && is_retryable(&error) => {
+                attempt += 1;
+                tracing::warn!(
+                    "{}; retrying in {}s ({}/{})",
+                    error,
+                    delay.as_secs_f32(),
+                    attempt,
+                    MAX_RETRIES
+                );
+                sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::LastFmApi { code, .. } => code.is_retryable(),
+        // Last.fm's "Operation failed" outages often come back as a plain 500.
+        Error::LastFm { status, .. } => matches!(status, 429 | 500) || *status >= 502,
+        _ => false,
+    }
+}
+
+async fn request<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    config: &LastFmConfig,
+    method: &str,
+    query: &str,
+) -> Result<T, Error> {
     let url = format!(
-        "https://ws.audioscrobbler.com/2.0/?method=user.getrecenttracks&user={}&api_key={}&limit={}&from={}&page={}&format=json",
-        username, api_key, PAGE_LIMIT, from, page
+        "{}/?method={}&user={}&api_key={}&{}&format=json",
+        config.api_root.trim_end_matches('/'),
+        method,
+        config.username,
+        config.api_key,
+        query
     );
 
-    let response = client.get(&url).send().await.map_err(|e| Error::Network {
+    // With a session the call is signed, which also shows private profiles.
+    // The signed URL carries the session key, so errors keep the plain one.
+    let request_url = match config.session() {
+        Some(session) => {
+            let mut params: Vec<(String, String)> = [
+                ("method", method),
+                ("user", config.username.as_str()),
+                ("api_key", config.api_key.as_str()),
+                ("sk", session.session_key.as_str()),
+            ]
+            .into_iter()
+            .chain(query.split('&').filter_map(|pair| pair.split_once('=')))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+            lastfm_signed::add_signature(&mut params, &session.app.api_secret);
+            params.push(("format".to_string(), "json".to_string()));
+
+            let root = format!("{}/", config.api_root.trim_end_matches('/'));
+            reqwest::Url::parse_with_params(&root, &params)
+                .map_err(|e| Error::InvalidConfig {
+                    field: "lastfm.api_root".to_string(),
+                    reason: e.to_string(),
+                })?
+                .to_string()
+        }
+        None => url.clone(),
+    };
+
+    let response = client
+        .get(&request_url)
+        .send()
+        .await
+        .map_err(|e| Error::Network {
+            url: url.clone(),
+            source: e.without_url(),
+        })?;
+
+    read_response(&config.name, response, url).await
+}
+
+/// Reads a Last.fm response as `T`, or as the error it reports. `url` is only
+/// used in errors, so it must not carry secrets.
+pub async fn read_response<T: DeserializeOwned>(
+    service: &str,
+    response: reqwest::Response,
+    url: String,
+) -> Result<T, Error> {
+    let status = response.status().as_u16();
+    let body = response.text().await.map_err(|e| Error::Network {
         url: url.clone(),
         source: e,
     })?;
 
-    if !response.status().is_success() {
-        let status = response.status().as_u16();
-        let body = response.text().await.unwrap_or_default();
-        return Err(Error::LastFm { status, url, body });
+    // Errors come as `{"error": 29, "message": "..."}`, whatever the status.
+    if let Ok(error) = serde_json::from_str::<ApiError>(&body) {
+        return Err(Error::LastFmApi {
+            service: service.to_string(),
+            code: LastFmErrorCode::from_code(error.error),
+            message: error.message,
+        });
     }
 
-    response
-        .json()
-        .await
-        .map_err(|e| Error::Network { url, source: e })
+    match serde_json::from_str(&body) {
+        Ok(response) if (200..300).contains(&status) => Ok(response),
+        _ => Err(Error::LastFm { status, url, body }),
+    }
 }
 
 fn parse_track(track: ApiTrack) -> Option<Scrobble> {
     let timestamp: i64 = track.date?.uts.parse().ok()?;
     let played_at = Utc.timestamp_opt(timestamp, 0).single()?;
-    Some(Scrobble::new(track.artist.text, track.name, played_at))
+
+    // Last.fm sends empty strings rather than leaving fields out.
+    let present = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+    let (album, album_mbid) = match track.album {
+        Some(album) => (present(album.text), present(album.mbid)),
+        None => (None, None),
+    };
+    let metadata = Metadata {
+        track_mbid: present(track.mbid),
+        artist_mbid: present(track.artist.mbid),
+        album_mbid,
+        artwork: track
+            .image
+            .into_iter()
+            .filter_map(|image| {
+                Some(Artwork {
+                    size: image.size,
+                    url: present(image.url)?,
+                })
+            })
+            .collect(),
+        loved: track.loved.map(|loved| loved == "1"),
+        duration: None,
+    };
+
+    Some(
+        Scrobble::new(track.artist.name, track.name, played_at)
+            .with_album(album)
+            .with_metadata(metadata),
+    )
 }
 
-fn calculate_timestamp(days: u64) -> u64 {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .expect("System time before UNIX epoch")
-        .as_secs();
-    now.saturating_sub(days * 86400)
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    error: u32,
+    message: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -120,12 +526,51 @@ struct ApiTrack {
     name: String,
     artist: ArtistInfo,
     date: Option<DateInfo>,
+    album: Option<AlbumInfo>,
+    mbid: Option<String>,
+    #[serde(default)]
+    image: Vec<ImageInfo>,
+    /// `"0"` or `"1"`, only with `extended=1`.
+    loved: Option<String>,
+    #[serde(rename = "@attr")]
+    attr: Option<TrackAttr>,
 }
 
+impl ApiTrack {
+    fn is_now_playing(&self) -> bool {
+        self.attr
+            .as_ref()
+            .is_some_and(|attr| attr.nowplaying.as_deref() == Some("true"))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackAttr {
+    nowplaying: Option<String>,
+}
+
+/// `extended=1` turns the artist into an object with a `name`; without it
+/// the name is in `#text`.
 #[derive(Debug, Deserialize)]
 struct ArtistInfo {
+    #[serde(alias = "#text")]
+    name: String,
+    mbid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumInfo {
+    #[serde(rename = "#text")]
+    text: Option<String>,
+    mbid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageInfo {
+    #[serde(default)]
+    size: String,
     #[serde(rename = "#text")]
-    text: String,
+    url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,246 @@
+use crate::aggregate::Statistics;
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::lastfm::{self, LastFmConfig};
+use crate::providers::types::{Album, Track};
+use serde::Deserialize;
+
+/// Time span of a chart, as Last.fm names it in `period`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Week,
+    Month,
+    ThreeMonths,
+    SixMonths,
+    Year,
+    Overall,
+}
+
+impl Period {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Period::Week => "7day",
+            Period::Month => "1month",
+            Period::ThreeMonths => "3month",
+            Period::SixMonths => "6month",
+            Period::Year => "12month",
+            Period::Overall => "overall",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chart {
+    Tracks,
+    Artists,
+    Albums,
+}
+
+/// Top lists Last.fm has already counted, used instead of downloading every
+/// scrobble when the period is long.
+#[derive(Debug, Clone)]
+pub struct ChartsConfig {
+    pub lastfm: LastFmConfig,
+    pub period: Period,
+    /// Which lists to show, in this order.
+    pub charts: Vec<Chart>,
+}
+
+impl ChartsConfig {
+    /// Enabled by `lastfm.period`. `lastfm.charts` picks the lists and
+    /// defaults to the top tracks alone.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, Error> {
+        let period = match settings.string("lastfm.period")?.as_deref() {
+            None => None,
+            Some("7day") => Some(Period::Week),
+            Some("1month") => Some(Period::Month),
+            Some("3month") => Some(Period::ThreeMonths),
+            Some("6month") => Some(Period::SixMonths),
+            Some("12month") => Some(Period::Year),
+            Some("overall") => Some(Period::Overall),
+            Some(_) => {
+                return Err(settings.invalid(
+                    "lastfm.period",
+                    "expected 7day, 1month, 3month, 6month, 12month or overall",
+                ));
+            }
+        };
+        let names = settings.strings("lastfm.charts")?;
+
+        let Some(period) = period else {
+            return Ok(None);
+        };
+        let mut charts = Vec::new();
+        for name in names {
+            let chart = match name.as_str() {
+                "tracks" => Chart::Tracks,
+                "artists" => Chart::Artists,
+                "albums" => Chart::Albums,
+                _ => {
+                    return Err(
+                        settings.invalid("lastfm.charts", "expected tracks, artists or albums")
+                    );
+                }
+            };
+            if !charts.contains(&chart) {
+                charts.push(chart);
+            }
+        }
+        if charts.is_empty() {
+            charts.push(Chart::Tracks);
+        }
+
+        let lastfm = lastfm::config(settings)?.ok_or_else(|| {
+            settings.invalid("lastfm.period", "needs lastfm.api_key and lastfm.username")
+        })?;
+
+        Ok(Some(Self {
+            lastfm,
+            period,
+            charts,
+        }))
+    }
+}
+
+/// Fetches the configured charts, `top_n` entries each. Last.fm does not say
+/// how many plays a period had in all, so `total_plays` only adds up the top
+/// tracks; `unique_tracks` is the length of the full track chart.
+pub async fn fetch_statistics(
+    client: &reqwest::Client,
+    config: &ChartsConfig,
+    top_n: usize,
+) -> Result<Statistics, Error> {
+    let mut statistics = Statistics {
+        top_tracks: Vec::new(),
+        top_artists: Vec::new(),
+        top_albums: Vec::new(),
+        total_plays: 0,
+        unique_tracks: 0,
+        now_playing: None,
+    };
+    let query = format!("period={}&limit={}", config.period.as_str(), top_n);
+
+    for chart in &config.charts {
+        match chart {
+            Chart::Tracks => {
+                let response: TopTracksResponse =
+                    lastfm::call_user_method(client, &config.lastfm, "user.gettoptracks", &query)
+                        .await?;
+                statistics.top_tracks = response
+                    .toptracks
+                    .track
+                    .into_iter()
+                    .take(top_n)
+                    .map(|track| {
+                        let track_plays = plays(&track.playcount);
+                        let track = Track {
+                            artist: track.artist.name,
+                            title: track.name,
+                        };
+                        (track, track_plays)
+                    })
+                    .collect();
+                statistics.total_plays = statistics.top_tracks.iter().map(|(_, n)| n).sum();
+                statistics.unique_tracks = response
+                    .toptracks
+                    .attr
+                    .and_then(|attr| attr.total.parse().ok())
+                    .unwrap_or(statistics.top_tracks.len());
+            }
+            Chart::Artists => {
+                let response: TopArtistsResponse =
+                    lastfm::call_user_method(client, &config.lastfm, "user.gettopartists", &query)
+                        .await?;
+                statistics.top_artists = response
+                    .topartists
+                    .artist
+                    .into_iter()
+                    .take(top_n)
+                    .map(|artist| {
+                        let artist_plays = plays(&artist.playcount);
+                        (artist.name, artist_plays)
+                    })
+                    .collect();
+            }
+            Chart::Albums => {
+                let response: TopAlbumsResponse =
+                    lastfm::call_user_method(client, &config.lastfm, "user.gettopalbums", &query)
+                        .await?;
+                statistics.top_albums = response
+                    .topalbums
+                    .album
+                    .into_iter()
+                    .take(top_n)
+                    .map(|album| {
+                        let album_plays = plays(&album.playcount);
+                        let album = Album {
+                            artist: album.artist.name,
+                            title: album.name,
+                        };
+                        (album, album_plays)
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    Ok(statistics)
+}
+
+fn plays(playcount: &str) -> usize {
+    playcount.parse().unwrap_or(0)
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTracksResponse {
+    toptracks: TopTracks,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTracks {
+    track: Vec<ChartEntry>,
+    #[serde(rename = "@attr")]
+    attr: Option<ChartAttr>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopArtistsResponse {
+    topartists: TopArtists,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopArtists {
+    artist: Vec<ChartArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopAlbumsResponse {
+    topalbums: TopAlbums,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopAlbums {
+    album: Vec<ChartEntry>,
+}
+
+/// A track or an album, with the artist it is by.
+#[derive(Debug, Deserialize)]
+struct ChartEntry {
+    name: String,
+    playcount: String,
+    artist: ChartArtist,
+}
+
+/// In the artist chart the play count is set; nested in a track or album
+/// it is not.
+#[derive(Debug, Deserialize)]
+struct ChartArtist {
+    name: String,
+    #[serde(default)]
+    playcount: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChartAttr {
+    total: String,
+}
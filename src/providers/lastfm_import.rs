@@ -0,0 +1,233 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::files;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use std::path::PathBuf;
+
+const CSV_DATE_FORMATS: &[&str] = &["%d %b %Y %H:%M", "%d %b %Y, %H:%M", "%Y-%m-%d %H:%M:%S"];
+const LOG_SUFFIX: &str = ".scrobbler.log";
+
+#[derive(Debug, Clone)]
+pub struct LastFmImportConfig {
+    /// Last.fm CSV exports and `.scrobbler.log` files, or folders and zips
+    /// holding them.
+    pub paths: Vec<PathBuf>,
+}
+
+pub struct LastFmImportSource {
+    config: LastFmImportConfig,
+}
+
+/// Reads `lastfm_import.paths`.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let paths = settings.paths("lastfm_import.paths")?;
+
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![Box::new(LastFmImportSource::new(
+        LastFmImportConfig { paths },
+    ))])
+}
+
+impl LastFmImportSource {
+    pub fn new(config: LastFmImportConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for LastFmImportSource {
+    fn name(&self) -> &str {
+        "Scrobble files"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            let scrobbles = read_scrobbles(&self.config)?;
+            Ok(scrobbles
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+pub fn read_scrobbles(config: &LastFmImportConfig) -> Result<Vec<Scrobble>, Error> {
+    let mut scrobbles = Vec::new();
+    files::read_all(&config.paths, is_scrobble_file, |path, content| {
+        if is_scrobbler_log(&path.to_string_lossy()) {
+            scrobbles.extend(parse_scrobbler_log(content));
+        } else {
+            let parsed = parse_csv(content).map_err(|e| files::input_error(path, e))?;
+            scrobbles.extend(parsed);
+        }
+        Ok(())
+    })?;
+    Ok(scrobbles)
+}
+
+/// Parses a Last.fm CSV export.
+///
+/// Without a header the columns are `artist, album, track, date`, the layout
+/// most export tools use. With a header, the `artist`, `track` (or `title`)
+/// and `uts` (or `date`/`utc_time`) columns are looked up by name. Rows without
+/// a usable date, such as a track that was playing during the export, are
+/// skipped.
+pub fn parse_csv(content: &str) -> Result<Vec<Scrobble>, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    let mut rows = reader.records();
+    let Some(first) = rows.next().transpose()? else {
+        return Ok(Vec::new());
+    };
+
+    let columns = CsvColumns::from_header(&first);
+    let mut scrobbles = Vec::new();
+    if columns.is_none() {
+        scrobbles.extend(CsvColumns::HEADERLESS.parse_row(&first));
+    }
+
+    let columns = columns.unwrap_or(CsvColumns::HEADERLESS);
+    for row in rows {
+        scrobbles.extend(columns.parse_row(&row?));
+    }
+
+    Ok(scrobbles)
+}
+
+/// Parses an Audioscrobbler portable player log (`.scrobbler.log`), as
+/// written by Rockbox and iPod scrobbling tools. Only tracks rated `L`
+/// (listened) are kept; `S` marks a skip.
+pub fn parse_scrobbler_log(content: &str) -> Vec<Scrobble> {
+    let mut timezone_known = true;
+    let mut scrobbles = Vec::new();
+
+    for line in content.lines() {
+        if let Some(header) = line.strip_prefix('#') {
+            if header.starts_with("TZ/") {
+                timezone_known = header.trim() == "TZ/UTC";
+            }
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 || fields[5] != "L" {
+            continue;
+        }
+
+        let Ok(timestamp) = fields[6].trim().parse::<i64>() else {
+            continue;
+        };
+        if let Some(played_at) = log_timestamp(timestamp, timezone_known) {
+            scrobbles.push(Scrobble::new(
+                fields[0].to_string(),
+                fields[2].to_string(),
+                played_at,
+            ));
+        }
+    }
+
+    scrobbles
+}
+
+/// With `#TZ/UNKNOWN` the device clock was on local time, so the number is
+/// read as a local wall-clock time rather than a UTC instant.
+fn log_timestamp(timestamp: i64, timezone_known: bool) -> Option<DateTime<Utc>> {
+    let instant = Utc.timestamp_opt(timestamp, 0).single()?;
+    if timezone_known {
+        return Some(instant);
+    }
+
+    let local = Local.from_local_datetime(&instant.naive_utc()).earliest()?;
+    Some(local.with_timezone(&Utc))
+}
+
+fn is_scrobble_file(path: &str) -> bool {
+    files::file_name(path).ends_with(".csv") || is_scrobbler_log(path)
+}
+
+fn is_scrobbler_log(path: &str) -> bool {
+    files::file_name(path).ends_with(LOG_SUFFIX)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CsvColumns {
+    artist: usize,
+    title: usize,
+    date: usize,
+    unix: bool,
+}
+
+impl CsvColumns {
+    const HEADERLESS: Self = Self {
+        artist: 0,
+        title: 2,
+        date: 3,
+        unix: false,
+    };
+
+    fn from_header(row: &csv::StringRecord) -> Option<Self> {
+        let find = |names: &[&str]| {
+            row.iter().position(|cell| {
+                names
+                    .iter()
+                    .any(|name| cell.trim().eq_ignore_ascii_case(name))
+            })
+        };
+
+        let artist = find(&["artist"])?;
+        let title = find(&["track", "title", "name"])?;
+        let (date, unix) = match find(&["uts"]) {
+            Some(index) => (index, true),
+            None => (find(&["date", "utc_time"])?, false),
+        };
+
+        Some(Self {
+            artist,
+            title,
+            date,
+            unix,
+        })
+    }
+
+    fn parse_row(&self, row: &csv::StringRecord) -> Option<Scrobble> {
+        let artist = row.get(self.artist)?.trim();
+        let title = row.get(self.title)?.trim();
+        let date = row.get(self.date)?.trim();
+        if artist.is_empty() || title.is_empty() {
+            return None;
+        }
+
+        let played_at = if self.unix {
+            Utc.timestamp_opt(date.parse().ok()?, 0).single()?
+        } else {
+            CSV_DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())?
+                .and_utc()
+        };
+
+        Some(Scrobble::new(
+            artist.to_string(),
+            title.to_string(),
+            played_at,
+        ))
+    }
+}
@@ -0,0 +1,232 @@
+use crate::errors::{Error, LastFmErrorCode};
+use crate::providers::lastfm;
+use crate::providers::types::Scrobble;
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::Write;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Most scrobbles `track.scrobble` takes in one request.
+pub const MAX_BATCH: usize = 50;
+
+/// Where Last.fm sends people to approve an app.
+const LASTFM_AUTH_URL: &str = "https://www.last.fm/api/auth/";
+/// `auth.getSession` error for a token nobody has approved yet.
+const TOKEN_NOT_AUTHORIZED: u32 = 14;
+
+/// An API account: the key, and the shared secret calls are signed with.
+#[derive(Debug, Clone)]
+pub struct App {
+    pub api_key: String,
+    pub api_secret: String,
+    pub api_root: String,
+}
+
+/// Credentials for the Last.fm methods that act on an account.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub app: App,
+    /// Key from `auth.getSession`; it does not expire.
+    pub session_key: String,
+}
+
+/// How many scrobbles of a batch Last.fm kept, and how many it dropped (for
+/// being too old, say, or filtered as spam).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Submitted {
+    pub accepted: usize,
+    pub ignored: usize,
+}
+
+/// Appends the `api_sig` for `params`.
+pub fn add_signature(params: &mut Vec<(String, String)>, secret: &str) {
+    let signature = sign(params, secret);
+    params.push(("api_sig".to_string(), signature));
+}
+
+/// The `api_sig` for `params`: every name and value, sorted by name and
+/// joined, followed by the secret, hashed with MD5. `format` is not signed.
+pub fn sign(params: &[(String, String)], secret: &str) -> String {
+    let mut sorted: Vec<&(String, String)> =
+        params.iter().filter(|(name, _)| name != "format").collect();
+    sorted.sort();
+
+    let mut hasher = Md5::new();
+    for (name, value) in sorted {
+        hasher.update(name.as_bytes());
+        hasher.update(value.as_bytes());
+    }
+    hasher.update(secret.as_bytes());
+
+    let mut output = String::new();
+    for byte in hasher.finalize() {
+        write!(&mut output, "{:02x}", byte).unwrap();
+    }
+    output
+}
+
+/// Submits up to [`MAX_BATCH`] plays with `track.scrobble`; a larger batch is
+/// an error.
+pub async fn scrobble(
+    client: &reqwest::Client,
+    session: &Session,
+    batch: &[Scrobble],
+) -> Result<Submitted, Error> {
+    if batch.len() > MAX_BATCH {
+        return Err(Error::Sync {
+            detail: format!(
+                "at most {} scrobbles can be sent at once, got {}",
+                MAX_BATCH,
+                batch.len()
+            ),
+        });
+    }
+
+    let mut params = vec![("method".to_string(), "track.scrobble".to_string())];
+    for (i, scrobble) in batch.iter().enumerate() {
+        params.push((format!("artist[{}]", i), scrobble.track.artist.clone()));
+        params.push((format!("track[{}]", i), scrobble.track.title.clone()));
+        params.push((
+            format!("timestamp[{}]", i),
+            scrobble.played_at.timestamp().to_string(),
+        ));
+        if let Some(album) = &scrobble.album {
+            params.push((format!("album[{}]", i), album.clone()));
+        }
+    }
+
+    let response: ScrobbleResponse =
+        call(client, &session.app, Some(&session.session_key), params).await?;
+    Ok(Submitted {
+        accepted: count(&response.scrobbles.attr.accepted),
+        ignored: count(&response.scrobbles.attr.ignored),
+    })
+}
+
+/// First step of the desktop auth flow: a token for the user to approve.
+pub async fn get_token(client: &reqwest::Client, app: &App) -> Result<String, Error> {
+    let params = vec![("method".to_string(), "auth.getToken".to_string())];
+    let response: TokenResponse = call(client, app, None, params).await?;
+    Ok(response.token)
+}
+
+/// The page where the user approves `token` for the app. Last.fm's is on
+/// www.last.fm; GNU FM servers serve it next to their API.
+pub fn auth_url(app: &App, token: &str) -> String {
+    let is_lastfm = app.api_root.trim_end_matches('/') == lastfm::DEFAULT_API_ROOT;
+    let base = match reqwest::Url::parse(&app.api_root).and_then(|root| root.join("/api/auth/")) {
+        Ok(url) if !is_lastfm => url.to_string(),
+        _ => LASTFM_AUTH_URL.to_string(),
+    };
+    format!("{}?api_key={}&token={}", base, app.api_key, token)
+}
+
+/// Exchanges an approved token for a session key, or returns `None` while
+/// the user has not approved it yet.
+pub async fn get_session(
+    client: &reqwest::Client,
+    app: &App,
+    token: &str,
+) -> Result<Option<String>, Error> {
+    let params = vec![
+        ("method".to_string(), "auth.getSession".to_string()),
+        ("token".to_string(), token.to_string()),
+    ];
+    match call::<SessionResponse>(client, app, None, params).await {
+        Ok(response) => Ok(Some(response.session.key)),
+        Err(Error::LastFmApi {
+            code: LastFmErrorCode::Other(TOKEN_NOT_AUTHORIZED),
+            ..
+        }) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Asks for the session every `interval` until the token is approved. Tokens
+/// expire after an hour, after which Last.fm reports an error.
+pub async fn wait_for_session(
+    client: &reqwest::Client,
+    app: &App,
+    token: &str,
+    interval: Duration,
+) -> Result<String, Error> {
+    loop {
+        if let Some(session_key) = get_session(client, app, token).await? {
+            return Ok(session_key);
+        }
+        sleep(interval).await;
+    }
+}
+
+/// POSTs a signed call. The key, session and signature travel in the body,
+/// so the URL in errors is just the API root.
+async fn call<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    app: &App,
+    session_key: Option<&str>,
+    mut params: Vec<(String, String)>,
+) -> Result<T, Error> {
+    params.push(("api_key".to_string(), app.api_key.clone()));
+    if let Some(session_key) = session_key {
+        params.push(("sk".to_string(), session_key.to_string()));
+    }
+    add_signature(&mut params, &app.api_secret);
+    params.push(("format".to_string(), "json".to_string()));
+
+    let url = format!("{}/", app.api_root.trim_end_matches('/'));
+    let response = client
+        .post(&url)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| Error::Network {
+            url: url.clone(),
+            source: e,
+        })?;
+
+    lastfm::read_response("Last.fm", response, url).await
+}
+
+/// Last.fm has sent these counts both as numbers and as strings.
+fn count(value: &Value) -> usize {
+    match value {
+        Value::Number(n) => n.as_u64().unwrap_or(0) as usize,
+        Value::String(s) => s.parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionResponse {
+    session: SessionInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionInfo {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrobbleResponse {
+    scrobbles: Scrobbles,
+}
+
+#[derive(Debug, Deserialize)]
+struct Scrobbles {
+    #[serde(rename = "@attr")]
+    attr: ScrobbleCounts,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrobbleCounts {
+    accepted: Value,
+    ignored: Value,
+}
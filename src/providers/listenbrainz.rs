@@ -0,0 +1,185 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const DEFAULT_API_ROOT: &str = "https://api.listenbrainz.org";
+const PAGE_LIMIT: usize = 1000;
+const RATE_LIMIT_MS: u64 = 200;
+
+#[derive(Debug, Clone)]
+pub struct ListenBrainzConfig {
+    pub username: String,
+    /// User token; listens are public, but a token raises the rate limit.
+    pub token: Option<String>,
+    pub api_root: String,
+}
+
+pub struct ListenBrainzSource {
+    config: ListenBrainzConfig,
+}
+
+/// Reads `listenbrainz.username`, plus the optional `listenbrainz.token` and
+/// `listenbrainz.api_root` for self-hosted servers.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let username = settings.string("listenbrainz.username")?;
+    let token = settings.string("listenbrainz.token")?;
+    let api_root = settings.string("listenbrainz.api_root")?;
+
+    match username {
+        Some(username) => Ok(vec![Box::new(ListenBrainzSource::new(
+            ListenBrainzConfig {
+                username,
+                token,
+                api_root: api_root.unwrap_or_else(|| DEFAULT_API_ROOT.to_string()),
+            },
+        ))]),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl ListenBrainzSource {
+    pub fn new(config: ListenBrainzConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for ListenBrainzSource {
+    fn name(&self) -> &str {
+        "ListenBrainz"
+    }
+
+    fn describe(&self) -> String {
+        format!("ListenBrainz ({})", self.config.username)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(fetch_scrobbles(client, &self.config, window))
+    }
+}
+
+/// Walks the listens backwards from the end of the window. The API refuses
+/// `min_ts` and `max_ts` together, so each page is bounded by `max_ts` only and
+/// paging stops once a page reaches back past the start of the window.
+///
+/// `max_ts` is exclusive, so the next page starts one second after the oldest
+/// listen; listens sharing that second that did not fit on the page are then
+/// not lost, and the ones already read are only kept once.
+pub async fn fetch_scrobbles(
+    client: &reqwest::Client,
+    config: &ListenBrainzConfig,
+    window: TimeWindow,
+) -> Result<Vec<Scrobble>, Error> {
+    let min_ts = window.from.timestamp();
+    let mut max_ts = window.to.timestamp() + 1;
+    let mut scrobbles = Vec::new();
+    let mut seen = HashSet::new();
+
+    loop {
+        let listens = fetch_page(client, config, max_ts).await?.payload.listens;
+        let page_size = listens.len();
+        let oldest = listens.iter().map(|listen| listen.listened_at).min();
+
+        scrobbles.extend(
+            listens
+                .into_iter()
+                .filter_map(parse_listen)
+                .filter(|s| window.contains(s.played_at))
+                .filter(|s| seen.insert((s.track.clone(), s.played_at))),
+        );
+
+        match oldest {
+            Some(oldest) if page_size >= PAGE_LIMIT && oldest > min_ts && oldest + 1 < max_ts => {
+                tracing::info!("Fetching ListenBrainz listens before {}", oldest);
+                max_ts = oldest + 1;
+                sleep(Duration::from_millis(RATE_LIMIT_MS)).await;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(scrobbles)
+}
+
+async fn fetch_page(
+    client: &reqwest::Client,
+    config: &ListenBrainzConfig,
+    max_ts: i64,
+) -> Result<ApiResponse, Error> {
+    let url = format!(
+        "{}/1/user/{}/listens?count={}&max_ts={}",
+        config.api_root.trim_end_matches('/'),
+        config.username,
+        PAGE_LIMIT,
+        max_ts
+    );
+
+    let mut request = client.get(&url);
+    if let Some(token) = &config.token {
+        request = request.header("Authorization", format!("Token {}", token));
+    }
+
+    let response = request.send().await.map_err(|e| Error::Network {
+        url: url.clone(),
+        source: e,
+    })?;
+
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::ListenBrainz { status, url, body });
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| Error::Network { url, source: e })
+}
+
+fn parse_listen(listen: ApiListen) -> Option<Scrobble> {
+    let played_at = Utc.timestamp_opt(listen.listened_at, 0).single()?;
+    let metadata = listen.track_metadata;
+    Some(Scrobble::new(
+        metadata.artist_name,
+        metadata.track_name,
+        played_at,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    payload: Payload,
+}
+
+#[derive(Debug, Deserialize)]
+struct Payload {
+    listens: Vec<ApiListen>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiListen {
+    listened_at: i64,
+    track_metadata: TrackMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackMetadata {
+    artist_name: String,
+    track_name: String,
+}
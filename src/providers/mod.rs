@@ -1,6 +1,24 @@
+pub mod apple_music_export;
+pub mod command;
+pub mod deezer;
+pub mod file_import;
+pub mod files;
+pub mod history;
 pub mod lastfm;
+pub mod lastfm_charts;
+pub mod lastfm_import;
+pub mod lastfm_signed;
+pub mod listenbrainz;
+pub mod mpd;
+pub mod registry;
+pub mod source;
+pub mod spotify;
+pub mod spotify_export;
+pub mod subsonic;
 pub mod types;
+pub mod webhook;
 pub mod youtube;
 pub mod youtube_http;
 pub mod youtube_json;
 pub mod youtube_parse;
+pub mod youtube_takeout;
@@ -0,0 +1,461 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::history;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow, play_needed};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf,
+    WriteHalf,
+};
+use tokio::time::{Instant, sleep};
+
+const DEFAULT_ADDRESS: &str = "localhost:6600";
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct MpdConfig {
+    /// `host:port`, or the path of MPD's Unix socket.
+    pub address: String,
+    pub password: Option<String>,
+    /// JSON Lines file the listener appends plays to and the source reads.
+    pub history: PathBuf,
+}
+
+pub struct MpdSource {
+    config: MpdConfig,
+}
+
+/// Enabled by `mpd.history`; `mpd.address` and `mpd.password` are only used by
+/// `music-stats listen`.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    match MpdConfig::from_settings(settings)? {
+        Some(config) => Ok(vec![Box::new(MpdSource::new(config))]),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl MpdConfig {
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, Error> {
+        let address = settings.string("mpd.address")?;
+        let password = settings.string("mpd.password")?;
+        let history = settings.string("mpd.history")?;
+
+        Ok(history.map(|history| Self {
+            address: address.unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+            password,
+            history: PathBuf::from(history),
+        }))
+    }
+}
+
+impl MpdSource {
+    pub fn new(config: MpdConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for MpdSource {
+    fn name(&self) -> &str {
+        "MPD"
+    }
+
+    fn describe(&self) -> String {
+        format!("MPD ({})", self.config.history.display())
+    }
+
+    /// Only plays recorded while `music-stats listen` was running are known.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: false,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            Ok(read_history(&self.config)?
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+/// Reads the plays `music-stats listen` has recorded so far.
+pub fn read_history(config: &MpdConfig) -> Result<Vec<Scrobble>, Error> {
+    history::read(&config.history)
+}
+
+fn append_history(config: &MpdConfig, scrobble: &Scrobble) -> Result<(), Error> {
+    history::append(&config.history, scrobble).map_err(|e| Error::Mpd {
+        address: config.address.clone(),
+        detail: format!("cannot write {}: {}", config.history.display(), e),
+    })
+}
+
+/// Records plays into the history file until the process is stopped,
+/// reconnecting whenever MPD goes away.
+pub async fn listen(config: &MpdConfig) -> Result<(), Error> {
+    loop {
+        let result = match connect(&config.address).await {
+            Ok(stream) => {
+                tracing::info!("Connected to MPD at {}", config.address);
+                record(stream, config, |scrobble| {
+                    tracing::info!(
+                        "Recorded {} - {}",
+                        scrobble.track.artist,
+                        scrobble.track.title
+                    );
+                    append_history(config, &scrobble)
+                })
+                .await
+            }
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(()) => tracing::warn!("MPD closed the connection"),
+            Err(error) => tracing::warn!("{}", error),
+        }
+        sleep(RECONNECT_DELAY).await;
+    }
+}
+
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+async fn connect(address: &str) -> Result<Box<dyn Connection>, Error> {
+    let error = |e: std::io::Error| Error::Mpd {
+        address: address.to_string(),
+        detail: e.to_string(),
+    };
+
+    #[cfg(unix)]
+    if address.starts_with('/') {
+        let stream = tokio::net::UnixStream::connect(address)
+            .await
+            .map_err(error)?;
+        return Ok(Box::new(stream));
+    }
+
+    let stream = tokio::net::TcpStream::connect(address)
+        .await
+        .map_err(error)?;
+    Ok(Box::new(stream))
+}
+
+/// Follows the player over one connection, calling `on_scrobble` for every
+/// track that passes the scrobble rule. Returns when MPD closes the connection.
+///
+/// `idle player` wakes up on every song change, pause and seek. While a track
+/// that has not counted yet is playing, the wait is cut short with `noidle`
+/// at the moment it would, so the play is recorded without waiting for the
+/// next event. The read of the `idle` response keeps going while `noidle` is
+/// sent, so a response that was already arriving is not lost.
+pub async fn record<S>(
+    stream: S,
+    config: &MpdConfig,
+    mut on_scrobble: impl FnMut(Scrobble) -> Result<(), Error>,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut client = Client::new(stream, &config.address);
+    if client.read_response().await?.is_none() {
+        return Ok(());
+    }
+    if let Some(password) = &config.password
+        && client
+            .command(&format!("password {}", quote(password)))
+            .await?
+            .is_none()
+    {
+        return Ok(());
+    }
+
+    let mut tracker = PlayTracker::default();
+    loop {
+        let Some(status) = client.command("status").await? else {
+            return Ok(());
+        };
+        let Some(song) = client.command("currentsong").await? else {
+            return Ok(());
+        };
+
+        let state = PlayerState::from_response(&status, &song);
+        if let Some(scrobble) = tracker.update(Instant::now(), Utc::now(), state) {
+            on_scrobble(scrobble)?;
+        }
+
+        client.send("idle player").await?;
+        let woke = match tracker.remaining(Instant::now()) {
+            Some(wait) => {
+                let Client {
+                    reader,
+                    writer,
+                    address,
+                } = &mut client;
+                let response = read_response(reader, address);
+                tokio::pin!(response);
+                tokio::select! {
+                    response = &mut response => response?,
+                    _ = sleep(wait) => {
+                        send(writer, address, "noidle").await?;
+                        response.await?
+                    }
+                }
+            }
+            None => client.read_response().await?,
+        };
+        if woke.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// What `status` and `currentsong` report about the player.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerState {
+    pub playing: bool,
+    pub elapsed: Option<Duration>,
+    pub song: Option<Song>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Song {
+    pub id: String,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl PlayerState {
+    fn from_response(status: &[(String, String)], song: &[(String, String)]) -> Self {
+        let status_field = |key: &str| field(status, key);
+        let song_field = |key: &str| field(song, key).map(str::to_string);
+        let seconds = |value: Option<&str>| {
+            value
+                .and_then(|v| v.parse::<f64>().ok())
+                .and_then(|v| Duration::try_from_secs_f64(v).ok())
+        };
+
+        let song = song_field("Id").map(|id| Song {
+            id,
+            artist: song_field("Artist").or_else(|| song_field("AlbumArtist")),
+            title: song_field("Title"),
+            album: song_field("Album"),
+            duration: seconds(field(song, "duration").or_else(|| field(song, "Time"))),
+        });
+
+        Self {
+            playing: status_field("state") == Some("play"),
+            elapsed: seconds(status_field("elapsed")),
+            song,
+        }
+    }
+}
+
+fn field<'a>(response: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    response
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Applies the usual scrobble rule to the updates seen over a connection:
+/// a track longer than 30 seconds counts once it has actually been playing for
+/// half its length or 4 minutes, whichever comes first. Paused time does not
+/// count, and the play is dated from when the track started.
+#[derive(Debug, Default)]
+pub struct PlayTracker {
+    current: Option<Current>,
+}
+
+#[derive(Debug)]
+struct Current {
+    song: Song,
+    started_at: DateTime<Utc>,
+    played: Duration,
+    playing_since: Option<Instant>,
+    last_elapsed: Option<Duration>,
+    scrobbled: bool,
+}
+
+impl PlayTracker {
+    /// Takes the player state seen at `now` (and `wall`, the same moment on the
+    /// clock) and returns the track that just passed the rule, if any.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        wall: DateTime<Utc>,
+        state: PlayerState,
+    ) -> Option<Scrobble> {
+        if let Some(current) = &mut self.current
+            && let Some(since) = current.playing_since.take()
+        {
+            current.played += now - since;
+        }
+        let scrobble = self.check();
+
+        let same_play = match (&self.current, &state.song) {
+            (Some(current), Some(song)) if current.song.id == song.id => {
+                // Starting the same song over, as with repeat, is a new play.
+                let restarted = match (current.last_elapsed, state.elapsed) {
+                    (Some(before), Some(after)) => after + Duration::from_secs(2) < before,
+                    _ => false,
+                };
+                !(restarted && current.scrobbled)
+            }
+            _ => false,
+        };
+
+        if !same_play {
+            self.current = state.song.map(|song| Current {
+                started_at: wall - state.elapsed.unwrap_or_default(),
+                song,
+                played: Duration::ZERO,
+                playing_since: None,
+                last_elapsed: None,
+                scrobbled: false,
+            });
+        }
+
+        if let Some(current) = &mut self.current {
+            current.last_elapsed = state.elapsed;
+            if state.playing {
+                current.playing_since = Some(now);
+            }
+        }
+
+        scrobble
+    }
+
+    /// How long the current track still has to play before it counts, or
+    /// `None` when nothing is waiting to be scrobbled.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        let current = self.current.as_ref()?;
+        if current.scrobbled {
+            return None;
+        }
+        let since = current.playing_since?;
+        let needed = play_needed(current.song.duration?)?;
+        Some(needed.saturating_sub(current.played + (now - since)))
+    }
+
+    fn check(&mut self) -> Option<Scrobble> {
+        let current = self.current.as_mut()?;
+        let needed = play_needed(current.song.duration?)?;
+        if current.scrobbled || current.played < needed {
+            return None;
+        }
+
+        current.scrobbled = true;
+        let song = &current.song;
+        Some(
+            Scrobble::new(
+                song.artist.clone()?,
+                song.title.clone()?,
+                current.started_at,
+            )
+            .with_album(song.album.clone()),
+        )
+    }
+}
+
+/// Quotes a command argument the way the MPD protocol expects.
+fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+struct Client<'a, S> {
+    reader: BufReader<ReadHalf<S>>,
+    writer: WriteHalf<S>,
+    address: &'a str,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Client<'a, S> {
+    fn new(stream: S, address: &'a str) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+            address,
+        }
+    }
+
+    async fn command(&mut self, command: &str) -> Result<Option<Vec<(String, String)>>, Error> {
+        self.send(command).await?;
+        self.read_response().await
+    }
+
+    async fn send(&mut self, command: &str) -> Result<(), Error> {
+        send(&mut self.writer, self.address, command).await
+    }
+
+    async fn read_response(&mut self) -> Result<Option<Vec<(String, String)>>, Error> {
+        read_response(&mut self.reader, self.address).await
+    }
+}
+
+fn mpd_error(address: &str, detail: impl std::fmt::Display) -> Error {
+    Error::Mpd {
+        address: address.to_string(),
+        detail: detail.to_string(),
+    }
+}
+
+async fn send(
+    writer: &mut (impl AsyncWrite + Unpin),
+    address: &str,
+    command: &str,
+) -> Result<(), Error> {
+    writer
+        .write_all(format!("{}\n", command).as_bytes())
+        .await
+        .map_err(|e| mpd_error(address, e))?;
+    writer.flush().await.map_err(|e| mpd_error(address, e))
+}
+
+/// Reads `key: value` lines up to `OK`. Returns `None` once MPD has closed
+/// the connection.
+async fn read_response(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    address: &str,
+) -> Result<Option<Vec<(String, String)>>, Error> {
+    let mut pairs = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| mpd_error(address, e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line == "OK" || line.starts_with("OK MPD ") {
+            return Ok(Some(pairs));
+        }
+        if let Some(message) = line.strip_prefix("ACK ") {
+            return Err(mpd_error(address, message));
+        }
+        if let Some((key, value)) = line.split_once(": ") {
+            pairs.push((key.to_string(), value.to_string()));
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::source::{ScrobbleSource, TimeWindow};
+use crate::providers::types::{Scrobble, Track};
+use crate::providers::{
+    apple_music_export, command, deezer, file_import, lastfm, lastfm_import, listenbrainz, mpd,
+    spotify, spotify_export, subsonic, webhook, youtube, youtube_takeout,
+};
+use std::fmt;
+
+/// Builds the sources a provider module finds configured in `settings`.
+pub type Factory = fn(&Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error>;
+
+/// Every known provider. New providers only need an entry here.
+const FACTORIES: &[Factory] = &[
+    lastfm::sources,
+    listenbrainz::sources,
+    subsonic::sources,
+    deezer::sources,
+    spotify::sources,
+    mpd::sources,
+    webhook::sources,
+    youtube::sources,
+    spotify_export::sources,
+    youtube_takeout::sources,
+    apple_music_export::sources,
+    lastfm_import::sources,
+    file_import::sources,
+    command::sources,
+];
+
+#[derive(Default)]
+pub struct Registry {
+    sources: Vec<Box<dyn ScrobbleSource>>,
+}
+
+impl Registry {
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        let mut registry = Self::default();
+        for factory in FACTORIES {
+            for source in factory(settings)? {
+                registry.register(source);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, source: Box<dyn ScrobbleSource>) {
+        self.sources.push(source);
+    }
+
+    pub fn sources(&self) -> &[Box<dyn ScrobbleSource>] {
+        &self.sources
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sources.iter().map(|source| source.name()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Fetches from every source in turn, stopping at the first failure.
+    pub async fn fetch_all(
+        &self,
+        client: &reqwest::Client,
+        window: TimeWindow,
+    ) -> Result<Vec<Scrobble>, Error> {
+        let mut all_scrobbles = Vec::new();
+
+        for source in &self.sources {
+            let scrobbles = source.fetch(client, window).await?;
+            tracing::info!("{}: {} scrobbles", source.name(), scrobbles.len());
+            all_scrobbles.extend(scrobbles);
+        }
+
+        Ok(all_scrobbles)
+    }
+
+    /// Asks each source in turn what is playing and returns the first answer.
+    /// This only decorates the output, so failures are logged and skipped.
+    pub async fn now_playing(&self, client: &reqwest::Client) -> Option<Track> {
+        for source in &self.sources {
+            match source.now_playing(client).await {
+                Ok(Some(track)) => return Some(track),
+                Ok(None) => {}
+                Err(error) => {
+                    tracing::warn!("{}: cannot tell what is playing: {}", source.name(), error)
+                }
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
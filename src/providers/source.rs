@@ -0,0 +1,81 @@
+use crate::errors::Error;
+use crate::providers::types::{Scrobble, Track};
+use chrono::{DateTime, Duration, Utc};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Tracks shorter than this are never scrobbled.
+const MIN_TRACK_LENGTH: std::time::Duration = std::time::Duration::from_secs(30);
+/// A track counts once it has played for half its length or this long.
+const MAX_PLAY_NEEDED: std::time::Duration = std::time::Duration::from_secs(240);
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A place scrobbles can be read from, such as a Last.fm account.
+pub trait ScrobbleSource: Send + Sync {
+    /// Short name used in logs, e.g. "Last.fm".
+    fn name(&self) -> &str;
+
+    /// Longer description shown by `doctor`, usually naming the account.
+    fn describe(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Returns the plays that happened inside `window`.
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>>;
+
+    /// The track playing right now, for sources that can tell. Usually asked
+    /// after `fetch`, so a source may answer from what that returned, but
+    /// charts mode asks without fetching.
+    fn now_playing<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+    ) -> BoxFuture<'a, Result<Option<Track>, Error>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// What a source can and cannot tell us about the plays it returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Plays carry the time they happened rather than an estimate.
+    pub exact_timestamps: bool,
+    /// Plays older than a few days are still available.
+    pub full_history: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl TimeWindow {
+    pub fn last_days(days: u64) -> Self {
+        let to = Utc::now();
+        Self {
+            from: to - Duration::days(days as i64),
+            to,
+        }
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        time >= self.from && time <= self.to
+    }
+}
+
+/// How long a track must play before it counts, following the usual scrobble
+/// rule: half its length or 4 minutes, whichever comes first. Tracks of 30
+/// seconds or less never count.
+pub fn play_needed(length: std::time::Duration) -> Option<std::time::Duration> {
+    if length <= MIN_TRACK_LENGTH {
+        return None;
+    }
+    Some((length / 2).min(MAX_PLAY_NEEDED))
+}
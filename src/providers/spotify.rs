@@ -0,0 +1,270 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::{Metadata, Scrobble};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::time::Duration;
+
+const DEFAULT_API_ROOT: &str = "https://api.spotify.com";
+const DEFAULT_TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const PAGE_LIMIT: usize = 50;
+const MAX_PAGES: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct SpotifyConfig {
+    pub client_id: String,
+    /// Left out for apps using PKCE, which have no secret.
+    pub client_secret: Option<String>,
+    /// Long-lived token from authorising the app with the
+    /// `user-read-recently-played` scope.
+    pub refresh_token: String,
+    pub api_root: String,
+    pub token_url: String,
+}
+
+pub struct SpotifySource {
+    config: SpotifyConfig,
+}
+
+/// Reads `spotify.client_id` and `spotify.refresh_token`, both needed, the
+/// optional `spotify.client_secret`, and `spotify.api_root` and
+/// `spotify.token_url` to point at other servers.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let client_id = settings.string("spotify.client_id")?;
+    let client_secret = settings.string("spotify.client_secret")?;
+    let refresh_token = settings.string("spotify.refresh_token")?;
+    let api_root = settings.string("spotify.api_root")?;
+    let token_url = settings.string("spotify.token_url")?;
+
+    match (client_id, refresh_token) {
+        (Some(client_id), Some(refresh_token)) => {
+            Ok(vec![Box::new(SpotifySource::new(SpotifyConfig {
+                client_id,
+                client_secret,
+                refresh_token,
+                api_root: api_root.unwrap_or_else(|| DEFAULT_API_ROOT.to_string()),
+                token_url: token_url.unwrap_or_else(|| DEFAULT_TOKEN_URL.to_string()),
+            }))])
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+impl SpotifySource {
+    pub fn new(config: SpotifyConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for SpotifySource {
+    fn name(&self) -> &str {
+        "Spotify"
+    }
+
+    /// Spotify only returns the last 50 plays.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: false,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(fetch_scrobbles(client, &self.config, window))
+    }
+}
+
+/// Trades the refresh token for an access token, then walks back from the end
+/// of the window with the `before` cursor until a page reaches past its start.
+/// Spotify takes only one of `before` and `after`, and walking back needs
+/// `before`, so `after` is never sent.
+pub async fn fetch_scrobbles(
+    client: &reqwest::Client,
+    config: &SpotifyConfig,
+    window: TimeWindow,
+) -> Result<Vec<Scrobble>, Error> {
+    let access_token = refresh_access_token(client, config).await?;
+    let mut before = window.to.timestamp_millis() + 1;
+    let mut scrobbles = Vec::new();
+
+    for page_number in 1..=MAX_PAGES {
+        let page = fetch_page(client, config, &access_token, before).await?;
+        let page_size = page.items.len();
+        let oldest = page.items.iter().map(|item| item.played_at).min();
+
+        scrobbles.extend(
+            page.items
+                .into_iter()
+                .map(parse_item)
+                .filter(|s| window.contains(s.played_at)),
+        );
+
+        let next = page
+            .cursors
+            .and_then(|cursors| cursors.before)
+            .and_then(|cursor| cursor.parse::<i64>().ok());
+        match (next, oldest) {
+            (Some(next), Some(oldest))
+                if page_size >= PAGE_LIMIT && oldest > window.from && next < before =>
+            {
+                if page_number == MAX_PAGES {
+                    tracing::warn!(
+                        "Stopped after {} pages of Spotify plays; plays before {} are not counted",
+                        MAX_PAGES,
+                        oldest
+                    );
+                    break;
+                }
+                tracing::info!("Fetching Spotify plays before {}", oldest);
+                before = next;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(scrobbles)
+}
+
+async fn refresh_access_token(
+    client: &reqwest::Client,
+    config: &SpotifyConfig,
+) -> Result<String, Error> {
+    let url = config.token_url.clone();
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", config.refresh_token.as_str()),
+    ];
+
+    let mut request = client.post(&url);
+    match &config.client_secret {
+        Some(secret) => request = request.basic_auth(&config.client_id, Some(secret)),
+        None => form.push(("client_id", config.client_id.as_str())),
+    }
+
+    let response = request
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| Error::Network {
+            url: url.clone(),
+            source: e,
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::Spotify { status, url, body });
+    }
+
+    let token: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| Error::Network { url, source: e })?;
+    if token
+        .refresh_token
+        .is_some_and(|rotated| rotated != config.refresh_token)
+    {
+        tracing::warn!("Spotify issued a new refresh token; update spotify.refresh_token");
+    }
+
+    Ok(token.access_token)
+}
+
+async fn fetch_page(
+    client: &reqwest::Client,
+    config: &SpotifyConfig,
+    access_token: &str,
+    before: i64,
+) -> Result<ApiResponse, Error> {
+    let url = format!(
+        "{}/v1/me/player/recently-played?limit={}&before={}",
+        config.api_root.trim_end_matches('/'),
+        PAGE_LIMIT,
+        before
+    );
+
+    let response = client
+        .get(&url)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .map_err(|e| Error::Network {
+            url: url.clone(),
+            source: e,
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::Spotify { status, url, body });
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| Error::Network { url, source: e })
+}
+
+/// Keeps Spotify's `played_at`, which is when the track finished, with the
+/// track's length next to it.
+fn parse_item(item: ApiItem) -> Scrobble {
+    let track = item.track;
+    let artist = track
+        .artists
+        .into_iter()
+        .map(|artist| artist.name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    Scrobble::new(artist, track.name, item.played_at)
+        .with_album(track.album.map(|album| album.name))
+        .with_metadata(Metadata {
+            duration: Some(Duration::from_millis(track.duration_ms)),
+            ..Metadata::default()
+        })
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    items: Vec<ApiItem>,
+    cursors: Option<Cursors>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cursors {
+    before: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiItem {
+    played_at: DateTime<Utc>,
+    track: ApiTrack,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiTrack {
+    name: String,
+    duration_ms: u64,
+    artists: Vec<ApiArtist>,
+    album: Option<ApiAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiArtist {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAlbum {
+    name: String,
+}
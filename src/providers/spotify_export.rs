@@ -0,0 +1,124 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::files;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+const FILE_PREFIX: &str = "Streaming_History_Audio_";
+
+#[derive(Debug, Clone)]
+pub struct SpotifyExportConfig {
+    /// Export files, folders holding them, or the zip Spotify sends.
+    pub paths: Vec<PathBuf>,
+    /// Plays shorter than this are dropped.
+    pub min_played_ms: u64,
+}
+
+pub struct SpotifyExportSource {
+    config: SpotifyExportConfig,
+}
+
+/// Reads `spotify_export.paths` and the optional
+/// `spotify_export.min_played_seconds`.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let paths = settings.paths("spotify_export.paths")?;
+    let min_played_seconds: u64 = settings
+        .parse("spotify_export.min_played_seconds")?
+        .unwrap_or(0);
+
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![Box::new(SpotifyExportSource::new(
+        SpotifyExportConfig {
+            paths,
+            min_played_ms: min_played_seconds.saturating_mul(1000),
+        },
+    ))])
+}
+
+impl SpotifyExportSource {
+    pub fn new(config: SpotifyExportConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for SpotifyExportSource {
+    fn name(&self) -> &str {
+        "Spotify export"
+    }
+
+    fn describe(&self) -> String {
+        format!("Spotify export ({} paths)", self.config.paths.len())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            let scrobbles = read_history(&self.config)?;
+            Ok(scrobbles
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+pub fn read_history(config: &SpotifyExportConfig) -> Result<Vec<Scrobble>, Error> {
+    let mut scrobbles = Vec::new();
+    files::read_all(&config.paths, is_history_file, |path, content| {
+        let parsed = parse_history(content, config.min_played_ms)
+            .map_err(|e| files::input_error(path, e))?;
+        scrobbles.extend(parsed);
+        Ok(())
+    })?;
+    Ok(scrobbles)
+}
+
+/// Parses one `Streaming_History_Audio_*.json` file. Podcast episodes and
+/// plays shorter than `min_played_ms` are skipped.
+pub fn parse_history(json: &str, min_played_ms: u64) -> Result<Vec<Scrobble>, serde_json::Error> {
+    let entries: Vec<HistoryEntry> = serde_json::from_str(json)?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| entry.ms_played >= min_played_ms)
+        .filter_map(parse_entry)
+        .collect())
+}
+
+/// `ts` is when playback stopped, so the play started `ms_played` earlier.
+fn parse_entry(entry: HistoryEntry) -> Option<Scrobble> {
+    let title = entry.master_metadata_track_name?;
+    let artist = entry.master_metadata_album_artist_name?;
+    let stopped_at: DateTime<Utc> = entry.ts.parse().ok()?;
+    let played_at = stopped_at - Duration::milliseconds(entry.ms_played as i64);
+    Some(Scrobble::new(artist, title, played_at))
+}
+
+fn is_history_file(path: &str) -> bool {
+    let name = files::file_name(path);
+    name.starts_with(FILE_PREFIX) && name.ends_with(".json")
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryEntry {
+    ts: String,
+    ms_played: u64,
+    master_metadata_track_name: Option<String>,
+    master_metadata_album_artist_name: Option<String>,
+}
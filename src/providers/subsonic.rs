@@ -0,0 +1,337 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::fmt::Write;
+
+const API_VERSION: &str = "1.16.1";
+const CLIENT_NAME: &str = "music-stats";
+const ALBUM_LIST_SIZE: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct SubsonicConfig {
+    pub name: String,
+    /// Server address without the `/rest` suffix, e.g. `https://music.example.com`.
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+pub struct SubsonicSource {
+    config: SubsonicConfig,
+}
+
+/// Reads `subsonic.url`, `subsonic.username` and `subsonic.password`, plus
+/// one more server for each `[subsonic.<name>]` section.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let mut sources: Vec<Box<dyn ScrobbleSource>> = Vec::new();
+
+    let url = settings.string("subsonic.url")?;
+    let username = settings.string("subsonic.username")?;
+    let password = settings.string("subsonic.password")?;
+    let name = settings.string("subsonic.name")?;
+
+    if let (Some(url), Some(username), Some(password)) = (url, username, password) {
+        sources.push(Box::new(SubsonicSource::new(SubsonicConfig {
+            name: name.unwrap_or_else(|| "Subsonic".to_string()),
+            url,
+            username,
+            password,
+        })));
+    }
+
+    for section in settings.sections("subsonic") {
+        let key = |option: &str| format!("subsonic.{}.{}", section, option);
+
+        sources.push(Box::new(SubsonicSource::new(SubsonicConfig {
+            url: settings.require(&key("url"))?,
+            username: settings.require(&key("username"))?,
+            password: settings.require(&key("password"))?,
+            name: settings.string(&key("name"))?.unwrap_or(section),
+        })));
+    }
+
+    Ok(sources)
+}
+
+impl SubsonicSource {
+    pub fn new(config: SubsonicConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for SubsonicSource {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} ({} on {})",
+            self.config.name, self.config.username, self.config.url
+        )
+    }
+
+    /// Now playing entries are only accurate to the minute, and the history
+    /// keeps the last play of each song rather than every play.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: false,
+            full_history: false,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(fetch_scrobbles(client, &self.config, window))
+    }
+}
+
+/// Subsonic has no play log. Servers implementing OpenSubsonic (Navidrome,
+/// Gonic) report when each album and song was last played, so recently played
+/// albums are walked, a page at a time, until one falls before the window. What is playing right
+/// now is added from `getNowPlaying`, which every server supports.
+pub async fn fetch_scrobbles(
+    client: &reqwest::Client,
+    config: &SubsonicConfig,
+    window: TimeWindow,
+) -> Result<Vec<Scrobble>, Error> {
+    let mut played: Vec<(String, Scrobble)> = Vec::new();
+    let mut offset = 0;
+
+    'pages: loop {
+        let albums: AlbumListResponse = call(
+            client,
+            config,
+            "getAlbumList2",
+            &[
+                ("type", "recent"),
+                ("size", &ALBUM_LIST_SIZE.to_string()),
+                ("offset", &offset.to_string()),
+            ],
+        )
+        .await?;
+        let page_size = albums.album_list.album.len();
+
+        for album in albums.album_list.album {
+            match album.played {
+                Some(last_played) if last_played >= window.from => {}
+                _ => break 'pages,
+            }
+
+            let details: AlbumResponse =
+                call(client, config, "getAlbum", &[("id", &album.id)]).await?;
+            for song in details.album.song {
+                if let Some(played_at) = song.played.filter(|t| window.contains(*t)) {
+                    played.push((song.id.clone(), song.into_scrobble(played_at)));
+                }
+            }
+        }
+
+        if page_size < ALBUM_LIST_SIZE {
+            break;
+        }
+        tracing::info!("Fetching more recently played albums from {}", config.name);
+        offset += ALBUM_LIST_SIZE;
+    }
+
+    let now = Utc::now();
+    let now_playing: NowPlayingResponse = call(client, config, "getNowPlaying", &[]).await?;
+    for entry in now_playing.now_playing.entry {
+        if entry.username.as_deref() != Some(config.username.as_str()) {
+            continue;
+        }
+
+        let started = now - chrono::Duration::minutes(entry.minutes_ago.unwrap_or(0));
+        let already_played = played
+            .iter()
+            .any(|(id, scrobble)| *id == entry.song.id && scrobble.played_at >= started);
+        if window.contains(started) && !already_played {
+            played.push((entry.song.id.clone(), entry.song.into_scrobble(started)));
+        }
+    }
+
+    Ok(played.into_iter().map(|(_, scrobble)| scrobble).collect())
+}
+
+async fn call<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    config: &SubsonicConfig,
+    method: &str,
+    params: &[(&str, &str)],
+) -> Result<T, Error> {
+    // The query carries the credentials, so errors only show the endpoint.
+    let url = format!("{}/rest/{}", config.url.trim_end_matches('/'), method);
+    let salt = salt();
+    let token = token(&config.password, &salt);
+
+    let auth = [
+        ("u", config.username.as_str()),
+        ("t", &token),
+        ("s", &salt),
+        ("v", API_VERSION),
+        ("c", CLIENT_NAME),
+        ("f", "json"),
+    ];
+    let request_url =
+        reqwest::Url::parse_with_params(&url, auth.iter().chain(params)).map_err(|e| {
+            Error::Subsonic {
+                url: url.clone(),
+                detail: e.to_string(),
+            }
+        })?;
+
+    let response = client
+        .get(request_url)
+        .send()
+        .await
+        .map_err(|e| Error::Network {
+            url: url.clone(),
+            source: e.without_url(),
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::Subsonic {
+            url,
+            detail: format!("status {}: {}", status.as_u16(), body),
+        });
+    }
+
+    let envelope: Envelope<T> = response.json().await.map_err(|e| Error::Network {
+        url: url.clone(),
+        source: e.without_url(),
+    })?;
+
+    match (envelope.response.status.as_str(), envelope.response.error) {
+        ("ok", _) => envelope.response.body.ok_or_else(|| Error::Subsonic {
+            url,
+            detail: "unexpected response".to_string(),
+        }),
+        (_, Some(error)) => Err(Error::Subsonic {
+            url,
+            detail: format!("error {}: {}", error.code, error.message),
+        }),
+        (status, None) => Err(Error::Subsonic {
+            url,
+            detail: format!("status {}", status),
+        }),
+    }
+}
+
+/// Salted token auth: `t = md5(password + s)`, with a new salt per request.
+pub fn token(password: &str, salt: &str) -> String {
+    let digest = Md5::digest(format!("{}{}", password, salt).as_bytes());
+    let mut output = String::with_capacity(digest.len() * 2);
+
+    for byte in digest {
+        write!(&mut output, "{:02x}", byte).unwrap();
+    }
+
+    output
+}
+
+fn salt() -> String {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).expect("Failed to generate a random salt");
+    bytes.iter().fold(String::new(), |mut salt, byte| {
+        write!(&mut salt, "{:02x}", byte).unwrap();
+        salt
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    #[serde(rename = "subsonic-response")]
+    response: ApiResponse<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    status: String,
+    error: Option<ApiError>,
+    #[serde(flatten)]
+    body: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: u32,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NowPlayingResponse {
+    #[serde(rename = "nowPlaying")]
+    now_playing: NowPlayingList,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumListResponse {
+    #[serde(rename = "albumList2")]
+    album_list: AlbumList,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumResponse {
+    album: AlbumDetails,
+}
+
+// Subsonic leaves out empty lists entirely, hence the defaults.
+#[derive(Debug, Deserialize)]
+struct NowPlayingList {
+    #[serde(default)]
+    entry: Vec<NowPlayingEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumList {
+    #[serde(default)]
+    album: Vec<ApiAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAlbum {
+    id: String,
+    played: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumDetails {
+    #[serde(default)]
+    song: Vec<ApiSong>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NowPlayingEntry {
+    username: Option<String>,
+    #[serde(rename = "minutesAgo")]
+    minutes_ago: Option<i64>,
+    #[serde(flatten)]
+    song: ApiSong,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiSong {
+    id: String,
+    title: String,
+    artist: Option<String>,
+    album: Option<String>,
+    played: Option<DateTime<Utc>>,
+}
+
+impl ApiSong {
+    fn into_scrobble(self, played_at: DateTime<Utc>) -> Scrobble {
+        let artist = self.artist.unwrap_or_else(|| "Unknown Artist".to_string());
+        Scrobble::new(artist, self.title, played_at).with_album(self.album)
+    }
+}
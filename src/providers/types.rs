@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Track {
@@ -6,10 +7,42 @@ pub struct Track {
     pub title: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Album {
+    pub artist: String,
+    pub title: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Scrobble {
     pub track: Track,
     pub played_at: DateTime<Utc>,
+    /// Kept out of `Track` so the same song from different releases is
+    /// counted together.
+    pub album: Option<String>,
+    /// Extra details some sources provide. Like the album, they stay out of
+    /// `Track` so they never split the play counts.
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// MusicBrainz IDs.
+    pub track_mbid: Option<String>,
+    pub artist_mbid: Option<String>,
+    pub album_mbid: Option<String>,
+    pub artwork: Vec<Artwork>,
+    /// Whether the listener has loved the track, when the source says.
+    pub loved: Option<bool>,
+    /// Length of the track, when the source says.
+    pub duration: Option<Duration>,
+}
+
+/// Cover art in one of the sizes a source offers, e.g. `small` or `extralarge`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artwork {
+    pub size: String,
+    pub url: String,
 }
 
 impl Scrobble {
@@ -17,6 +50,18 @@ impl Scrobble {
         Self {
             track: Track { artist, title },
             played_at,
+            album: None,
+            metadata: Metadata::default(),
         }
     }
+
+    pub fn with_album(mut self, album: Option<String>) -> Self {
+        self.album = album.filter(|a| !a.trim().is_empty());
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
 }
@@ -0,0 +1,364 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::history;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow, play_needed};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8787";
+/// Plex attaches the cover art to its webhooks.
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+/// Plex sends `media.scrobble` once 90% of a track has played.
+const PLEX_SCROBBLE_POINT: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// `host:port` to accept webhooks on.
+    pub address: String,
+    /// Shared secret expected as the `token` query parameter, if set.
+    pub token: Option<String>,
+    /// Only plays by this account are kept; media servers often have several.
+    pub username: Option<String>,
+    /// JSON Lines file the receiver appends plays to and the source reads.
+    pub history: PathBuf,
+}
+
+pub struct WebhookSource {
+    config: WebhookConfig,
+}
+
+/// Enabled by `webhook.history`; the other `webhook.*` options are only used
+/// by `music-stats webhook`.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    match WebhookConfig::from_settings(settings)? {
+        Some(config) => Ok(vec![Box::new(WebhookSource::new(config))]),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl WebhookConfig {
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, Error> {
+        let address = settings.string("webhook.address")?;
+        let token = settings.string("webhook.token")?;
+        let username = settings.string("webhook.username")?;
+        let history = settings.string("webhook.history")?;
+
+        Ok(history.map(|history| Self {
+            address: address.unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+            token,
+            username,
+            history: PathBuf::from(history),
+        }))
+    }
+}
+
+impl WebhookSource {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for WebhookSource {
+    fn name(&self) -> &str {
+        "Media server"
+    }
+
+    fn describe(&self) -> String {
+        format!("Media server webhooks ({})", self.config.history.display())
+    }
+
+    /// Plays are dated from when the server reported them, less the time the
+    /// track had been playing, and only exist while the receiver was running.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: false,
+            full_history: false,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            Ok(history::read(&self.config.history)?
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+/// Accepts webhooks on `config.address` until the process is stopped.
+pub async fn listen(config: WebhookConfig) -> Result<(), Error> {
+    let listener = TcpListener::bind(&config.address)
+        .await
+        .map_err(|e| Error::Webhook {
+            detail: format!("cannot listen on {}: {}", config.address, e),
+        })?;
+    tracing::info!("Waiting for webhooks on {}", config.address);
+    serve(listener, config).await
+}
+
+/// Serves webhooks from an already bound listener.
+pub async fn serve(listener: TcpListener, config: WebhookConfig) -> Result<(), Error> {
+    let config = Arc::new(config);
+
+    loop {
+        let (stream, _) = listener.accept().await.map_err(|e| Error::Webhook {
+            detail: e.to_string(),
+        })?;
+        let config = Arc::clone(&config);
+
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let config = Arc::clone(&config);
+                async move { Ok::<_, hyper::Error>(handle(&config, request).await) }
+            });
+            if let Err(error) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!("Webhook connection failed: {}", error);
+            }
+        });
+    }
+}
+
+async fn handle(config: &WebhookConfig, request: Request<Incoming>) -> Response<Full<Bytes>> {
+    if request.method() != Method::POST {
+        return reply(StatusCode::METHOD_NOT_ALLOWED, "POST webhooks here");
+    }
+
+    if let Some(token) = &config.token {
+        let given = request.uri().query().and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find(|(name, _)| name == "token")
+                .map(|(_, value)| value)
+        });
+        if given.as_deref() != Some(token.as_str()) {
+            return reply(StatusCode::UNAUTHORIZED, "wrong or missing token");
+        }
+    }
+
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = match Limited::new(request.into_body(), MAX_BODY_BYTES)
+        .collect()
+        .await
+    {
+        Ok(body) => body.to_bytes(),
+        Err(error) => return reply(StatusCode::BAD_REQUEST, &error.to_string()),
+    };
+
+    let event = match parse_event(content_type.as_deref(), body).await {
+        Ok(event) => event,
+        Err(error) => {
+            tracing::warn!("{}", error);
+            return reply(StatusCode::BAD_REQUEST, &error.to_string());
+        }
+    };
+
+    match event.into_scrobble(config.username.as_deref(), Utc::now()) {
+        Some(scrobble) => {
+            if let Err(error) = history::append(&config.history, &scrobble) {
+                tracing::warn!("Cannot write {}: {}", config.history.display(), error);
+                return reply(StatusCode::INTERNAL_SERVER_ERROR, "cannot record play");
+            }
+            tracing::info!(
+                "Recorded {} - {}",
+                scrobble.track.artist,
+                scrobble.track.title
+            );
+            reply(StatusCode::OK, "recorded")
+        }
+        None => reply(StatusCode::OK, "ignored"),
+    }
+}
+
+fn reply(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(message.to_string())));
+    *response.status_mut() = status;
+    response
+}
+
+/// A playback event from one of the supported media servers.
+#[derive(Debug)]
+pub enum Event {
+    Plex(PlexPayload),
+    Jellyfin(JellyfinPayload),
+}
+
+/// Reads a webhook body. Plex posts `multipart/form-data` with the JSON in a
+/// `payload` field; Jellyfin's webhook plugin posts the JSON as is.
+pub async fn parse_event(content_type: Option<&str>, body: Bytes) -> Result<Event, Error> {
+    let invalid = |detail: String| Error::Webhook { detail };
+
+    let json = match content_type.and_then(|ct| multer::parse_boundary(ct).ok()) {
+        Some(boundary) => {
+            let mut multipart =
+                multer::Multipart::with_reader(std::io::Cursor::new(body), boundary);
+            let mut payload = None;
+            while let Some(field) = multipart
+                .next_field()
+                .await
+                .map_err(|e| invalid(e.to_string()))?
+            {
+                if field.name() == Some("payload") {
+                    payload = Some(field.bytes().await.map_err(|e| invalid(e.to_string()))?);
+                    break;
+                }
+            }
+            payload.ok_or_else(|| invalid("multipart body without a payload field".into()))?
+        }
+        None => body,
+    };
+
+    let value: serde_json::Value =
+        serde_json::from_slice(&json).map_err(|e| invalid(format!("invalid JSON: {}", e)))?;
+    if value.get("event").is_some() {
+        serde_json::from_value(value)
+            .map(Event::Plex)
+            .map_err(|e| invalid(format!("unexpected Plex payload: {}", e)))
+    } else if value.get("NotificationType").is_some() {
+        serde_json::from_value(value)
+            .map(Event::Jellyfin)
+            .map_err(|e| invalid(format!("unexpected Jellyfin payload: {}", e)))
+    } else {
+        Err(invalid("not a Plex or Jellyfin webhook".into()))
+    }
+}
+
+impl Event {
+    /// Returns the play this event completes, if it is a finished music track
+    /// by `username` (or anyone, when no username is configured). `now` is
+    /// when the event arrived.
+    pub fn into_scrobble(self, username: Option<&str>, now: DateTime<Utc>) -> Option<Scrobble> {
+        match self {
+            Event::Plex(payload) => payload.into_scrobble(username, now),
+            Event::Jellyfin(payload) => payload.into_scrobble(username, now),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlexPayload {
+    event: String,
+    #[serde(rename = "Account")]
+    account: Option<PlexAccount>,
+    #[serde(rename = "Metadata")]
+    metadata: Option<PlexMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexAccount {
+    title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlexMetadata {
+    #[serde(rename = "type")]
+    kind: String,
+    title: String,
+    /// Album artist.
+    grandparent_title: Option<String>,
+    /// Track artist, when it differs from the album artist.
+    original_title: Option<String>,
+    /// Album.
+    parent_title: Option<String>,
+    /// Milliseconds.
+    duration: Option<u64>,
+}
+
+impl PlexPayload {
+    fn into_scrobble(self, username: Option<&str>, now: DateTime<Utc>) -> Option<Scrobble> {
+        if self.event != "media.scrobble" {
+            return None;
+        }
+        let account = self.account.and_then(|account| account.title);
+        if username.is_some_and(|username| account.as_deref() != Some(username)) {
+            return None;
+        }
+
+        let metadata = self.metadata.filter(|m| m.kind == "track")?;
+        let artist = metadata.original_title.or(metadata.grandparent_title)?;
+        let played = metadata
+            .duration
+            .map(|ms| Duration::from_millis(ms).mul_f64(PLEX_SCROBBLE_POINT))
+            .unwrap_or_default();
+
+        Some(
+            Scrobble::new(artist, metadata.title, started_at(now, played))
+                .with_album(metadata.parent_title),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinPayload {
+    notification_type: String,
+    item_type: Option<String>,
+    name: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    notification_username: Option<String>,
+    played_to_completion: Option<bool>,
+    playback_position_ticks: Option<u64>,
+    run_time_ticks: Option<u64>,
+}
+
+impl JellyfinPayload {
+    fn into_scrobble(self, username: Option<&str>, now: DateTime<Utc>) -> Option<Scrobble> {
+        if self.notification_type != "PlaybackStop" || self.item_type.as_deref() != Some("Audio") {
+            return None;
+        }
+        if username.is_some_and(|username| self.notification_username.as_deref() != Some(username))
+        {
+            return None;
+        }
+
+        // Jellyfin counts time in ticks of 100 nanoseconds.
+        let ticks = |ticks: u64| Duration::from_nanos(ticks.saturating_mul(100));
+        let played = self.playback_position_ticks.map(ticks).unwrap_or_default();
+        let length = self.run_time_ticks.map(ticks);
+
+        // Jellyfin reports every stop, including skips, so apply the scrobble
+        // rule unless it says the track was played to the end.
+        let finished = self.played_to_completion == Some(true)
+            || length
+                .and_then(play_needed)
+                .is_some_and(|needed| played >= needed);
+        if !finished {
+            return None;
+        }
+
+        let played = match (self.played_to_completion, length) {
+            (Some(true), Some(length)) if played.is_zero() => length,
+            _ => played,
+        };
+        Some(
+            Scrobble::new(self.artist?, self.name?, started_at(now, played)).with_album(self.album),
+        )
+    }
+}
+
+fn started_at(now: DateTime<Utc>, played: Duration) -> DateTime<Utc> {
+    now - chrono::Duration::from_std(played).unwrap_or_default()
+}
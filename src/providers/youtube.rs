@@ -1,24 +1,64 @@
+use crate::config::Settings;
 use crate::errors::Error;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
 use crate::providers::types::Scrobble;
 use crate::providers::{youtube_http, youtube_json, youtube_parse};
-use chrono::{Duration, Utc};
+
+pub struct YouTubeSource {
+    cookie: String,
+}
+
+/// Reads `youtube.cookie`.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    Ok(settings
+        .string("youtube.cookie")?
+        .map(|cookie| Box::new(YouTubeSource::new(cookie)) as Box<dyn ScrobbleSource>)
+        .into_iter()
+        .collect())
+}
+
+impl YouTubeSource {
+    pub fn new(cookie: String) -> Self {
+        Self { cookie }
+    }
+}
+
+impl ScrobbleSource for YouTubeSource {
+    fn name(&self) -> &str {
+        "YouTube Music"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: false,
+            full_history: false,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(fetch_scrobbles(client, &self.cookie, window))
+    }
+}
 
 pub async fn fetch_scrobbles(
     client: &reqwest::Client,
     cookie: &str,
-    days: u64,
+    window: TimeWindow,
 ) -> Result<Vec<Scrobble>, Error> {
     let html = youtube_http::fetch_history_page(client, cookie).await?;
     let json = youtube_parse::extract_json_from_html(&html)?;
     let scrobbles = youtube_json::parse_scrobbles(&json)?;
-    let filtered = filter_by_date(scrobbles, days);
+    let filtered = filter_by_date(scrobbles, window);
     Ok(filtered)
 }
 
-fn filter_by_date(scrobbles: Vec<Scrobble>, days: u64) -> Vec<Scrobble> {
-    let cutoff = Utc::now() - Duration::days(days as i64);
+fn filter_by_date(scrobbles: Vec<Scrobble>, window: TimeWindow) -> Vec<Scrobble> {
     scrobbles
         .into_iter()
-        .filter(|s| s.played_at >= cutoff)
+        .filter(|s| s.played_at >= window.from)
         .collect()
 }
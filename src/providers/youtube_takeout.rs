@@ -0,0 +1,283 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::files;
+use crate::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+const MUSIC_HEADER: &str = "YouTube Music";
+const WATCHED_PREFIXES: &[&str] = &[
+    "Watched ",
+    "Has visto ",
+    "Assistiu a ",
+    "Hai guardato ",
+    "Vous avez regardé ",
+];
+const TOPIC_SUFFIX: &str = " - Topic";
+const HTML_DATE_FORMATS: &[&str] = &["%b %d, %Y, %I:%M:%S %p", "%d %b %Y, %H:%M:%S"];
+
+/// Time zone names Takeout prints after HTML timestamps, with their offsets.
+const TIME_ZONES: &[(&str, i32)] = &[
+    ("UTC", 0),
+    ("GMT", 0),
+    ("BST", 3600),
+    ("CET", 3600),
+    ("CEST", 7200),
+    ("EST", -5 * 3600),
+    ("EDT", -4 * 3600),
+    ("CST", -6 * 3600),
+    ("CDT", -5 * 3600),
+    ("MST", -7 * 3600),
+    ("MDT", -6 * 3600),
+    ("PST", -8 * 3600),
+    ("PDT", -7 * 3600),
+];
+
+#[derive(Debug, Clone)]
+pub struct YouTubeTakeoutConfig {
+    /// `watch-history.json` or `.html` files, folders holding them, or the
+    /// Takeout zip.
+    pub paths: Vec<PathBuf>,
+}
+
+pub struct YouTubeTakeoutSource {
+    config: YouTubeTakeoutConfig,
+}
+
+/// Reads `youtube_takeout.paths`.
+pub fn sources(settings: &Settings) -> Result<Vec<Box<dyn ScrobbleSource>>, Error> {
+    let paths = settings.paths("youtube_takeout.paths")?;
+
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![Box::new(YouTubeTakeoutSource::new(
+        YouTubeTakeoutConfig { paths },
+    ))])
+}
+
+impl YouTubeTakeoutSource {
+    pub fn new(config: YouTubeTakeoutConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ScrobbleSource for YouTubeTakeoutSource {
+    fn name(&self) -> &str {
+        "YouTube Takeout"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            exact_timestamps: true,
+            full_history: true,
+        }
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            let scrobbles = read_history(&self.config)?;
+            Ok(scrobbles
+                .into_iter()
+                .filter(|s| window.contains(s.played_at))
+                .collect())
+        })
+    }
+}
+
+pub fn read_history(config: &YouTubeTakeoutConfig) -> Result<Vec<Scrobble>, Error> {
+    let mut scrobbles = Vec::new();
+    files::read_all(&config.paths, is_history_file, |path, content| {
+        let is_html = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("html"));
+        let history = if is_html {
+            parse_html(content)
+        } else {
+            parse_json(content).map_err(|e| files::input_error(path, e))?
+        };
+        if history.undated > 0 {
+            tracing::warn!(
+                "{}: skipped {} YouTube Music entries whose date could not be read",
+                path.display(),
+                history.undated
+            );
+        }
+        scrobbles.extend(history.scrobbles);
+        Ok(())
+    })?;
+    Ok(scrobbles)
+}
+
+/// Parses `watch-history.json`, keeping only YouTube Music plays.
+pub fn parse_json(json: &str) -> Result<History, serde_json::Error> {
+    let entries: Vec<JsonEntry> = serde_json::from_str(json)?;
+
+    let mut history = History::default();
+    for entry in entries {
+        if entry.header != MUSIC_HEADER || entry.title_url.is_none() {
+            continue;
+        }
+        let Ok(played_at) = entry.time.parse::<DateTime<Utc>>() else {
+            history.undated += 1;
+            continue;
+        };
+        let artist = entry.subtitles.first().map(|s| s.name.as_str());
+        history
+            .scrobbles
+            .push(to_scrobble(&entry.title, artist, played_at));
+    }
+    Ok(history)
+}
+
+/// YouTube Music plays read from a watch history file.
+#[derive(Debug, Default)]
+pub struct History {
+    pub scrobbles: Vec<Scrobble>,
+    /// Music entries left out because their date or time zone is not one we
+    /// can read.
+    pub undated: usize,
+}
+
+/// Parses `watch-history.html`, the format Takeout uses unless JSON is
+/// requested. Its timestamps are only as precise as the printed date.
+pub fn parse_html(html: &str) -> History {
+    let header = Regex::new(r#"<p class="mdl-typography--title">(.*?)<br"#).unwrap();
+    let content = Regex::new(r#"<div class="content-cell[^"]*body-1">(.*?)</div>"#).unwrap();
+    let link = Regex::new(r#"<a href="[^"]*">(.*?)</a>"#).unwrap();
+    let tag = Regex::new(r"<[^>]+>").unwrap();
+
+    let mut history = History::default();
+    for cell in html.split(r#"<div class="outer-cell"#).skip(1) {
+        let Some(title) = header.captures(cell) else {
+            continue;
+        };
+        if decode_entities(title[1].trim()) != MUSIC_HEADER {
+            continue;
+        }
+
+        let Some(body) = content.captures(cell) else {
+            continue;
+        };
+        let links: Vec<String> = link
+            .captures_iter(&body[1])
+            .map(|c| decode_entities(&c[1]))
+            .collect();
+        let Some(track) = links.first() else {
+            continue;
+        };
+        let played_at = body[1]
+            .split("<br>")
+            .map(|part| decode_entities(tag.replace_all(part, "").trim()))
+            .filter(|part| !part.is_empty())
+            .last()
+            .and_then(|date| parse_html_date(&date));
+        let Some(played_at) = played_at else {
+            history.undated += 1;
+            continue;
+        };
+
+        history.scrobbles.push(to_scrobble(
+            track,
+            links.get(1).map(String::as_str),
+            played_at,
+        ));
+    }
+    history
+}
+
+fn to_scrobble(title: &str, channel: Option<&str>, played_at: DateTime<Utc>) -> Scrobble {
+    let title = WATCHED_PREFIXES
+        .iter()
+        .find_map(|prefix| title.strip_prefix(prefix))
+        .unwrap_or(title);
+    let artist = channel
+        .map(|name| name.strip_suffix(TOPIC_SUFFIX).unwrap_or(name))
+        .unwrap_or("Unknown Artist");
+    Scrobble::new(artist.to_string(), title.to_string(), played_at)
+}
+
+fn parse_html_date(date: &str) -> Option<DateTime<Utc>> {
+    let normalized = date.replace(['\u{202f}', '\u{a0}'], " ");
+    let (local, zone) = normalized.rsplit_once(' ')?;
+    let offset = parse_zone(zone)?;
+
+    HTML_DATE_FORMATS.iter().find_map(|format| {
+        let naive = NaiveDateTime::parse_from_str(local, format).ok()?;
+        let local = offset.from_local_datetime(&naive).single()?;
+        Some(local.with_timezone(&Utc))
+    })
+}
+
+/// A zone name from [`TIME_ZONES`], or an offset such as `GMT+05:30`,
+/// `UTC-3` or `+0100`, which is how Takeout prints the other zones.
+fn parse_zone(zone: &str) -> Option<FixedOffset> {
+    if let Some((_, seconds)) = TIME_ZONES.iter().find(|(name, _)| *name == zone) {
+        return FixedOffset::east_opt(*seconds);
+    }
+
+    let offset = zone
+        .strip_prefix("GMT")
+        .or_else(|| zone.strip_prefix("UTC"))
+        .unwrap_or(zone);
+    let (sign, digits) = if let Some(digits) = offset.strip_prefix('+') {
+        (1, digits)
+    } else if let Some(digits) = offset
+        .strip_prefix('-')
+        .or_else(|| offset.strip_prefix('\u{2212}'))
+    {
+        (-1, digits)
+    } else {
+        return None;
+    };
+
+    let (hours, minutes) = match digits.split_once(':') {
+        Some(parts) => parts,
+        None if digits.len() > 2 => digits.split_at(digits.len() - 2),
+        None => (digits, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn is_history_file(path: &str) -> bool {
+    let name = files::file_name(path);
+    name == "watch-history.json" || name == "watch-history.html"
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonEntry {
+    header: String,
+    title: String,
+    title_url: Option<String>,
+    #[serde(default)]
+    subtitles: Vec<Subtitle>,
+    time: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subtitle {
+    name: String,
+}
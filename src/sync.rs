@@ -0,0 +1,240 @@
+use crate::config::Settings;
+use crate::errors::Error;
+use crate::providers::history;
+use crate::providers::lastfm::{self, LastFmConfig};
+use crate::providers::lastfm_signed::{self, MAX_BATCH, Session, Submitted};
+use crate::providers::source::TimeWindow;
+use crate::providers::types::Scrobble;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// YouTube Music only knows the day of a play, which we date at noon.
+const DEFAULT_TOLERANCE_MINUTES: i64 = 12 * 60;
+
+/// Settings for `music-stats sync`, which scrobbles YouTube Music plays to
+/// Last.fm.
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub youtube_cookie: String,
+    /// Account the plays are scrobbled to, read to skip what is already there.
+    pub lastfm: LastFmConfig,
+    pub session: Session,
+    /// JSON Lines file of every play submitted so far, so reruns never send
+    /// one twice.
+    pub ledger: PathBuf,
+    /// How far apart a play and a scrobble can be and still be the same.
+    pub tolerance: Duration,
+}
+
+impl SyncConfig {
+    /// Enabled by `sync.ledger`. Needs the YouTube cookie and the Last.fm API
+    /// key, secret, session key and username.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, Error> {
+        let ledger = settings.string("sync.ledger")?;
+        let tolerance_minutes: i64 = settings
+            .parse("sync.tolerance_minutes")?
+            .unwrap_or(DEFAULT_TOLERANCE_MINUTES);
+
+        let Some(ledger) = ledger else {
+            return Ok(None);
+        };
+        if tolerance_minutes < 0 {
+            return Err(settings.invalid("sync.tolerance_minutes", "must not be negative"));
+        }
+
+        let missing = |key: &str| settings.invalid(key, "required by sync");
+        let lastfm = LastFmConfig {
+            name: "Last.fm".to_string(),
+            api_key: settings.require("lastfm.api_key")?,
+            username: settings.require("lastfm.username")?,
+            api_root: settings
+                .string("lastfm.api_root")?
+                .unwrap_or_else(|| lastfm::DEFAULT_API_ROOT.to_string()),
+            max_pages: None,
+            api_secret: Some(
+                settings
+                    .string("lastfm.api_secret")?
+                    .ok_or_else(|| missing("lastfm.api_secret"))?,
+            ),
+            session_key: Some(
+                settings
+                    .string("lastfm.session_key")?
+                    .ok_or_else(|| missing("lastfm.session_key"))?,
+            ),
+        };
+
+        Ok(Some(Self {
+            youtube_cookie: settings
+                .string("youtube.cookie")?
+                .ok_or_else(|| missing("youtube.cookie"))?,
+            session: lastfm.session().expect("secret and session key are set"),
+            lastfm,
+            ledger: PathBuf::from(ledger),
+            tolerance: Duration::minutes(tolerance_minutes),
+        }))
+    }
+}
+
+/// Plays still to be scrobbled, and how many were left out because they are
+/// already on Last.fm or in the ledger.
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub pending: Vec<Scrobble>,
+    pub on_lastfm: usize,
+    pub in_ledger: usize,
+}
+
+/// Reads the Last.fm account and the ledger for the window and works out
+/// which of `plays` have not been scrobbled yet.
+pub async fn plan(
+    client: &reqwest::Client,
+    config: &SyncConfig,
+    plays: Vec<Scrobble>,
+    window: TimeWindow,
+) -> Result<Plan, Error> {
+    // Widen the window so plays near its start still find their match.
+    let wide = TimeWindow {
+        from: window.from - config.tolerance,
+        to: window.to + config.tolerance,
+    };
+    let scrobbled = lastfm::fetch_scrobbles(client, &config.lastfm, wide).await?;
+    let ledger: Vec<Scrobble> = history::read(&config.ledger)?
+        .into_iter()
+        .filter(|s| wide.contains(s.played_at))
+        .collect();
+
+    Ok(unsynced(plays, &ledger, &scrobbled, config.tolerance))
+}
+
+/// Leaves out each play that matches a ledger entry or a Last.fm scrobble of
+/// the same track within `tolerance`. Every entry stands for one play, so a
+/// track played twice is only skipped twice if it was scrobbled twice.
+///
+/// What we sent earlier is on Last.fm as well as in the ledger; those
+/// scrobbles are paired off with their ledger entries first so that they are
+/// not counted twice.
+pub fn unsynced(
+    plays: Vec<Scrobble>,
+    ledger: &[Scrobble],
+    scrobbled: &[Scrobble],
+    tolerance: Duration,
+) -> Plan {
+    let mut on_lastfm = Matcher::new(scrobbled, tolerance);
+    for entry in ledger {
+        on_lastfm.take(entry);
+    }
+    let mut in_ledger = Matcher::new(ledger, tolerance);
+
+    let mut plan = Plan::default();
+    for play in plays {
+        if in_ledger.take(&play) {
+            plan.in_ledger += 1;
+        } else if on_lastfm.take(&play) {
+            plan.on_lastfm += 1;
+        } else {
+            plan.pending.push(play);
+        }
+    }
+    plan
+}
+
+/// Scrobbles `pending` in batches of 50, adding each batch to the ledger as
+/// soon as Last.fm has it. Plays Last.fm ignores are recorded too, since
+/// sending them again would not change its mind.
+pub async fn submit(
+    client: &reqwest::Client,
+    config: &SyncConfig,
+    pending: &[Scrobble],
+    now: DateTime<Utc>,
+) -> Result<Submitted, Error> {
+    let mut total = Submitted::default();
+    let dated = for_lastfm(pending, now);
+
+    for (batch, (plays, dated)) in pending
+        .chunks(MAX_BATCH)
+        .zip(dated.chunks(MAX_BATCH))
+        .enumerate()
+    {
+        let submitted = lastfm_signed::scrobble(client, &config.session, dated).await?;
+        for play in plays {
+            history::append(&config.ledger, play).map_err(|e| Error::Sync {
+                detail: format!(
+                    "scrobbled batch {} but cannot record it in {}: {}",
+                    batch + 1,
+                    config.ledger.display(),
+                    e
+                ),
+            })?;
+        }
+
+        tracing::info!(
+            "Scrobbled batch {}: {} accepted, {} ignored",
+            batch + 1,
+            submitted.accepted,
+            submitted.ignored
+        );
+        total.accepted += submitted.accepted;
+        total.ignored += submitted.ignored;
+    }
+
+    Ok(total)
+}
+
+/// Last.fm drops a scrobble that has the same timestamp as one it already
+/// has for that track, and rejects ones from the future. Plays sharing an
+/// estimated time are spread a second apart and nothing is dated past `now`.
+fn for_lastfm(plays: &[Scrobble], now: DateTime<Utc>) -> Vec<Scrobble> {
+    let mut seen: HashMap<DateTime<Utc>, i64> = HashMap::new();
+    plays
+        .iter()
+        .map(|play| {
+            let at = play.played_at.min(now);
+            let offset = seen.entry(at).or_insert(0);
+            let mut play = play.clone();
+            play.played_at = at - Duration::seconds(*offset);
+            *offset += 1;
+            play
+        })
+        .collect()
+}
+
+/// Entries that can each be matched with one play.
+struct Matcher<'a> {
+    entries: Vec<(&'a Scrobble, bool)>,
+    tolerance: Duration,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(entries: &'a [Scrobble], tolerance: Duration) -> Self {
+        Self {
+            entries: entries.iter().map(|entry| (entry, false)).collect(),
+            tolerance,
+        }
+    }
+
+    /// Marks the closest unused entry for the same track as used.
+    fn take(&mut self, play: &Scrobble) -> bool {
+        let closest = self
+            .entries
+            .iter_mut()
+            .filter(|(entry, used)| !used && same_track(entry, play))
+            .map(|(entry, used)| ((entry.played_at - play.played_at).abs(), used))
+            .filter(|(distance, _)| *distance <= self.tolerance)
+            .min_by_key(|(distance, _)| *distance);
+
+        match closest {
+            Some((_, used)) => {
+                *used = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Last.fm corrects capitalisation, so names are compared without it.
+fn same_track(a: &Scrobble, b: &Scrobble) -> bool {
+    a.track.artist.to_lowercase() == b.track.artist.to_lowercase()
+        && a.track.title.to_lowercase() == b.track.title.to_lowercase()
+}
@@ -1,6 +1,6 @@
 use chrono::Utc;
 use music_stats::aggregate::compute_statistics;
-use music_stats::providers::types::Scrobble;
+use music_stats::providers::types::{Metadata, Scrobble};
 
 #[test]
 fn empty_scrobbles_returns_empty_statistics() {
@@ -88,3 +88,23 @@ fn top_n_larger_than_unique_tracks() {
     assert_eq!(stats.top_tracks.len(), 2);
     assert_eq!(stats.unique_tracks, 2);
 }
+
+#[test]
+fn album_and_metadata_do_not_split_counts() {
+    let scrobbles = vec![
+        Scrobble::new("Artist".into(), "Track".into(), Utc::now())
+            .with_album(Some("Single".into())),
+        Scrobble::new("Artist".into(), "Track".into(), Utc::now())
+            .with_album(Some("Album".into()))
+            .with_metadata(Metadata {
+                track_mbid: Some("b9b3b8a5-1a56-4a9c-9a2b-9f0d1ad5b6c1".into()),
+                loved: Some(true),
+                ..Metadata::default()
+            }),
+    ];
+
+    let stats = compute_statistics(scrobbles, 10);
+
+    assert_eq!(stats.unique_tracks, 1);
+    assert_eq!(stats.top_tracks[0].1, 2);
+}
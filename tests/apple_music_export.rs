@@ -0,0 +1,76 @@
+use music_stats::providers::apple_music_export::{
+    AppleMusicExportConfig, parse_activity, read_activity,
+};
+use std::io::Write;
+
+const ACTIVITY: &str = "\u{feff}Album Name,Artist Name,Content Name,End Reason Type,Event End Timestamp,Event Start Timestamp,Event Type,Media Type,Play Duration Milliseconds,Song Name
+OK Computer,Radiohead,Airbag,NATURAL_END_OF_TRACK,2024-01-15T12:38:44.000Z,2024-01-15T12:34:00.123Z,PLAY_END,AUDIO,284000,Airbag
+OK Computer,Radiohead,Lucky,TRACK_SKIPPED_FORWARDS,2024-01-15T12:39:00.000Z,2024-01-15T12:38:50.000Z,PLAY_END,AUDIO,10000,Lucky
+Dummy,Portishead,Roads,PLAYBACK_MANUALLY_PAUSED,2024-01-15T13:02:00.000Z,,PLAY_END,AUDIO,120000,Roads
+Dummy,Portishead,Sour Times,,,2024-01-15T13:05:00.000Z,PLAY_START,AUDIO,,Sour Times
+,Some Band,Music Video,NATURAL_END_OF_TRACK,2024-01-15T14:00:00.000Z,2024-01-15T13:56:00.000Z,PLAY_END,VIDEO,240000,Music Video
+";
+
+const NEWER_ACTIVITY: &str = "\
+Container Album Name,Container Artist Name,End Reason Type,Event Start Timestamp,Event Type,Play Duration Milliseconds,Song Name
+Dummy,Portishead,NATURAL_END_OF_TRACK,2024-02-01T08:00:00Z,PLAY_END,305000,Roads
+Dummy,,NATURAL_END_OF_TRACK,2024-02-01T08:06:00Z,PLAY_END,200000,No artist
+";
+
+#[test]
+fn keeps_finished_and_long_enough_plays() {
+    let scrobbles = parse_activity(ACTIVITY, 30_000).unwrap();
+
+    let titles: Vec<&str> = scrobbles.iter().map(|s| s.track.title.as_str()).collect();
+    assert_eq!(titles, vec!["Airbag", "Roads"]);
+    assert_eq!(scrobbles[0].track.artist, "Radiohead");
+    assert_eq!(scrobbles[0].album.as_deref(), Some("OK Computer"));
+    assert_eq!(scrobbles[0].played_at.timestamp(), 1705322040);
+    // No start time, so it is worked out from the end and the play length.
+    assert_eq!(scrobbles[1].played_at.timestamp(), 1705323600);
+}
+
+#[test]
+fn applies_threshold_to_interrupted_plays() {
+    let scrobbles = parse_activity(ACTIVITY, 180_000).unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.title, "Airbag");
+}
+
+#[test]
+fn reads_newer_column_names() {
+    let scrobbles = parse_activity(NEWER_ACTIVITY, 30_000).unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.artist, "Portishead");
+    assert_eq!(scrobbles[0].album.as_deref(), Some("Dummy"));
+}
+
+#[test]
+fn finds_the_csv_inside_the_export_zip() {
+    let path = std::env::temp_dir().join("music-stats-apple-music.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    zip.start_file(
+        "Apple_Media_Services/Apple Music Activity/Apple Music Play Activity.csv",
+        options,
+    )
+    .unwrap();
+    zip.write_all(ACTIVITY.as_bytes()).unwrap();
+    zip.start_file(
+        "Apple_Media_Services/Apple Music Activity/Other.csv",
+        options,
+    )
+    .unwrap();
+    zip.write_all(b"not,relevant\n").unwrap();
+    zip.finish().unwrap();
+
+    let config = AppleMusicExportConfig {
+        paths: vec![path],
+        min_played_ms: 30_000,
+    };
+    let scrobbles = read_activity(&config).unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+}
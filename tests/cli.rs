@@ -0,0 +1,97 @@
+use clap::Parser;
+use music_stats::cli::{AuthService, Cli, Command};
+
+#[test]
+fn defaults_to_run_without_subcommand() {
+    let cli = Cli::try_parse_from(["music-stats"]).unwrap();
+    assert!(cli.command.is_none());
+}
+
+#[test]
+fn parses_subcommands() {
+    let cli = Cli::try_parse_from(["music-stats", "stats"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Stats)));
+
+    let cli = Cli::try_parse_from(["music-stats", "listen"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Listen)));
+
+    let cli = Cli::try_parse_from(["music-stats", "webhook"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Webhook)));
+
+    let cli = Cli::try_parse_from(["music-stats", "upload", "--input", "out.txt"]).unwrap();
+    match cli.command {
+        Some(Command::Upload { input, .. }) => assert_eq!(input.unwrap().to_str(), Some("out.txt")),
+        other => panic!("unexpected command: {:?}", other),
+    }
+}
+
+#[test]
+fn flags_become_overrides() {
+    let cli = Cli::try_parse_from([
+        "music-stats",
+        "fetch",
+        "--days",
+        "30",
+        "--top-n",
+        "10",
+        "--lastfm-username",
+        "user",
+    ])
+    .unwrap();
+
+    let overrides = cli.config.overrides();
+    assert_eq!(overrides.days, Some(30));
+    assert_eq!(overrides.top_n, Some(10));
+    assert_eq!(overrides.lastfm_username.as_deref(), Some("user"));
+    assert!(overrides.gist_id.is_none());
+}
+
+#[test]
+fn rejects_invalid_days() {
+    let result = Cli::try_parse_from(["music-stats", "--days", "soon"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn dry_run_belongs_to_the_commands_that_write() {
+    let cli = Cli::try_parse_from(["music-stats", "run", "--dry-run"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Run { dry_run: true })));
+
+    let cli = Cli::try_parse_from(["music-stats", "upload", "--dry-run"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Upload { dry_run: true, .. })
+    ));
+}
+
+#[test]
+fn rejects_dry_run_where_nothing_is_written() {
+    assert!(Cli::try_parse_from(["music-stats", "stats", "--dry-run"]).is_err());
+    assert!(Cli::try_parse_from(["music-stats", "doctor", "--dry-run"]).is_err());
+    assert!(Cli::try_parse_from(["music-stats", "--dry-run", "run"]).is_err());
+}
+
+#[test]
+fn config_flags_still_work_before_the_subcommand() {
+    let cli = Cli::try_parse_from(["music-stats", "--days", "7", "stats"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Stats)));
+    assert_eq!(cli.config.overrides().days, Some(7));
+}
+
+#[test]
+fn parses_sync() {
+    let cli = Cli::try_parse_from(["music-stats", "sync", "--dry-run"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Sync { dry_run: true })));
+}
+
+#[test]
+fn parses_auth_lastfm() {
+    let cli = Cli::try_parse_from(["music-stats", "auth", "lastfm"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Auth {
+            service: AuthService::Lastfm
+        })
+    ));
+    assert!(Cli::try_parse_from(["music-stats", "auth"]).is_err());
+}
@@ -0,0 +1,116 @@
+use chrono::{TimeZone, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::command::{CommandConfig, run};
+use music_stats::providers::source::TimeWindow;
+use std::time::Duration;
+
+fn config(script: &str) -> CommandConfig {
+    CommandConfig {
+        name: "player".into(),
+        program: "sh".into(),
+        args: vec!["-c".into(), script.into()],
+        timeout: Duration::from_secs(5),
+    }
+}
+
+fn window() -> TimeWindow {
+    TimeWindow {
+        from: Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+        to: Utc.with_ymd_and_hms(2025, 3, 8, 0, 0, 0).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn reads_scrobbles_from_stdout() {
+    let script = r#"
+echo '{"artist": "Björk", "title": "Jóga", "album": "Homogenic", "played_at": "2025-03-02T10:00:00Z"}'
+echo
+echo '{"artist": "Björk", "title": "Hunter", "played_at": "2025-03-02T10:05:00+01:00"}'
+"#;
+
+    let scrobbles = run(&config(script), window()).await.unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.title, "Jóga");
+    assert_eq!(scrobbles[0].album.as_deref(), Some("Homogenic"));
+    assert_eq!(
+        scrobbles[1].played_at,
+        Utc.with_ymd_and_hms(2025, 3, 2, 9, 5, 0).unwrap()
+    );
+    assert_eq!(scrobbles[1].album, None);
+}
+
+#[tokio::test]
+async fn passes_the_window_in_the_environment() {
+    let script = r#"echo "{\"artist\": \"$MUSIC_STATS_FROM\", \"title\": \"t\", \"played_at\": \"$MUSIC_STATS_TO\"}""#;
+
+    let scrobbles = run(&config(script), window()).await.unwrap();
+
+    assert_eq!(scrobbles[0].track.artist, "2025-03-01T00:00:00+00:00");
+    assert_eq!(scrobbles[0].played_at, window().to);
+}
+
+#[tokio::test]
+async fn reports_failures_with_stderr() {
+    let error = run(&config("echo 'no such library' >&2; exit 3"), window())
+        .await
+        .unwrap_err();
+
+    match error {
+        Error::Command { name, detail } => {
+            assert_eq!(name, "player");
+            assert!(detail.contains("no such library"), "{}", detail);
+        }
+        other => panic!("expected a command error, got {}", other),
+    }
+}
+
+#[tokio::test]
+async fn reports_the_line_that_cannot_be_read() {
+    let script = r#"
+echo '{"artist": "Björk", "title": "Jóga", "played_at": "2025-03-02T10:00:00Z"}'
+echo 'not json'
+"#;
+
+    let error = run(&config(script), window()).await.unwrap_err();
+
+    assert!(format!("{}", error).contains("line 2"), "{}", error);
+}
+
+#[tokio::test]
+async fn kills_commands_that_run_too_long() {
+    let config = CommandConfig {
+        timeout: Duration::from_millis(200),
+        ..config("sleep 10")
+    };
+
+    let error = run(&config, window()).await.unwrap_err();
+
+    assert!(format!("{}", error).contains("timed out"), "{}", error);
+}
+
+#[tokio::test]
+async fn keeps_stderr_of_commands_that_time_out() {
+    let config = CommandConfig {
+        timeout: Duration::from_millis(500),
+        ..config("echo 'token expired, retrying' >&2; exec sleep 10")
+    };
+
+    let error = run(&config, window()).await.unwrap_err();
+    let message = format!("{}", error);
+
+    assert!(message.contains("timed out"), "{}", message);
+    assert!(message.contains("token expired, retrying"), "{}", message);
+}
+
+#[tokio::test]
+async fn reports_missing_programs() {
+    let config = CommandConfig {
+        program: "./does-not-exist".into(),
+        ..config("")
+    };
+
+    let error = run(&config, window()).await.unwrap_err();
+
+    assert!(format!("{}", error).contains("cannot start"), "{}", error);
+}
@@ -1,5 +1,8 @@
-use music_stats::config::load;
+use music_stats::config::{Config, Overrides, load, load_with, store};
+use music_stats::errors::Error;
+use music_stats::providers::lastfm_charts::{Chart, Period};
 use std::env;
+use std::path::PathBuf;
 
 fn clear_env() {
     unsafe {
@@ -7,9 +10,68 @@ fn clear_env() {
         env::remove_var("GH_TOKEN");
         env::remove_var("LASTFM_API_KEY");
         env::remove_var("LASTFM_USERNAME");
+        env::remove_var("LASTFM_API_ROOT");
+        env::remove_var("LASTFM_NAME");
+        env::remove_var("LASTFM_MAX_PAGES");
+        env::remove_var("LASTFM_API_SECRET");
+        env::remove_var("LASTFM_SESSION_KEY");
+        env::remove_var("LASTFM_PERIOD");
+        env::remove_var("LASTFM_CHARTS");
+        env::remove_var("SYNC_LEDGER");
+        env::remove_var("SYNC_TOLERANCE_MINUTES");
         env::remove_var("YOUTUBE_COOKIE");
+        env::remove_var("DEEZER_ACCESS_TOKEN");
+        env::remove_var("DEEZER_API_ROOT");
+        env::remove_var("SPOTIFY_CLIENT_ID");
+        env::remove_var("SPOTIFY_CLIENT_SECRET");
+        env::remove_var("SPOTIFY_REFRESH_TOKEN");
+        env::remove_var("SPOTIFY_API_ROOT");
+        env::remove_var("SPOTIFY_TOKEN_URL");
+        env::remove_var("SUBSONIC_URL");
+        env::remove_var("SUBSONIC_USERNAME");
+        env::remove_var("SUBSONIC_PASSWORD");
+        env::remove_var("SUBSONIC_NAME");
+        env::remove_var("LISTENBRAINZ_USERNAME");
+        env::remove_var("LISTENBRAINZ_TOKEN");
+        env::remove_var("LISTENBRAINZ_API_ROOT");
+        env::remove_var("SPOTIFY_EXPORT_PATHS");
+        env::remove_var("SPOTIFY_EXPORT_MIN_PLAYED_SECONDS");
+        env::remove_var("YOUTUBE_TAKEOUT_PATHS");
+        env::remove_var("LASTFM_IMPORT_PATHS");
+        env::remove_var("APPLE_MUSIC_EXPORT_PATHS");
+        env::remove_var("APPLE_MUSIC_EXPORT_MIN_PLAYED_SECONDS");
         env::remove_var("DAYS");
         env::remove_var("TOP_N");
+        env::remove_var("MUSIC_STATS_CONFIG");
+        env::set_var("XDG_CONFIG_HOME", "/nonexistent");
+        env::set_var("XDG_CONFIG_DIRS", "/nonexistent");
+    }
+}
+
+fn has_source(config: &Config, name: &str) -> bool {
+    config.sources.names().contains(&name)
+}
+
+fn describe(config: &Config) -> Vec<String> {
+    config
+        .sources
+        .sources()
+        .iter()
+        .filter(|source| source.name() == "Last.fm")
+        .map(|source| source.describe())
+        .collect()
+}
+
+fn write_config(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("music-stats-{}.toml", name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn with_file(path: PathBuf) -> Overrides {
+    Overrides {
+        config_path: Some(path),
+        ..Overrides::default()
     }
 }
 
@@ -71,8 +133,8 @@ fn loads_with_lastfm_provider() {
     assert!(result.is_ok());
 
     let config = result.unwrap();
-    assert!(config.provider.lastfm().is_some());
-    assert!(config.provider.youtube_cookie().is_none());
+    assert!(has_source(&config, "Last.fm"));
+    assert!(!has_source(&config, "YouTube Music"));
 }
 
 #[test]
@@ -87,8 +149,8 @@ fn loads_with_youtube_provider() {
     assert!(result.is_ok());
 
     let config = result.unwrap();
-    assert!(config.provider.lastfm().is_none());
-    assert!(config.provider.youtube_cookie().is_some());
+    assert!(!has_source(&config, "Last.fm"));
+    assert!(has_source(&config, "YouTube Music"));
 }
 
 #[test]
@@ -105,8 +167,8 @@ fn loads_with_both_providers() {
     assert!(result.is_ok());
 
     let config = result.unwrap();
-    assert!(config.provider.lastfm().is_some());
-    assert!(config.provider.youtube_cookie().is_some());
+    assert!(has_source(&config, "Last.fm"));
+    assert!(has_source(&config, "YouTube Music"));
 }
 
 #[test]
@@ -218,7 +280,7 @@ fn ignores_empty_string_values() {
     }
 
     let config = load().unwrap();
-    assert!(config.provider.youtube_cookie().is_none());
+    assert!(!has_source(&config, "YouTube Music"));
 }
 
 #[test]
@@ -231,6 +293,576 @@ fn ignores_partial_lastfm_config() {
     }
 
     let config = load().unwrap();
-    assert!(config.provider.lastfm().is_none());
-    assert!(config.provider.youtube_cookie().is_some());
+    assert!(!has_source(&config, "Last.fm"));
+    assert!(has_source(&config, "YouTube Music"));
+}
+
+#[test]
+fn overrides_take_precedence_over_env() {
+    clear_env();
+    set_required_env();
+    unsafe {
+        env::set_var("LASTFM_API_KEY", "key");
+        env::set_var("LASTFM_USERNAME", "env_user");
+        env::set_var("DAYS", "30");
+    }
+
+    let overrides = Overrides {
+        lastfm_username: Some("flag_user".into()),
+        days: Some(3),
+        ..Overrides::default()
+    };
+
+    let config = load_with(&overrides).unwrap();
+    assert_eq!(config.days, 3);
+    assert_eq!(describe(&config), vec!["Last.fm (flag_user)"]);
+}
+
+#[test]
+fn overrides_alone_are_enough() {
+    clear_env();
+
+    let overrides = Overrides {
+        youtube_cookie: Some("cookie".into()),
+        ..Overrides::default()
+    };
+
+    let config = load_with(&overrides).unwrap();
+    assert!(has_source(&config, "YouTube Music"));
+    assert!(config.gist().is_err());
+}
+
+#[test]
+fn validates_overridden_values() {
+    clear_env();
+
+    let overrides = Overrides {
+        youtube_cookie: Some("cookie".into()),
+        top_n: Some(0),
+        ..Overrides::default()
+    };
+
+    let result = load_with(&overrides);
+    assert!(format!("{}", result.unwrap_err()).contains("TOP_N"));
+}
+
+#[test]
+fn reads_everything_from_config_file() {
+    clear_env();
+    let path = write_config(
+        "full",
+        r#"
+days = 14
+top_n = 3
+
+[gist]
+id = "file_gist"
+token = "file_token"
+
+[lastfm]
+api_key = "key"
+username = "file_user"
+
+[youtube]
+cookie = "cookie"
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert_eq!(config.days, 14);
+    assert_eq!(config.top_n, 3);
+    assert_eq!(config.gist().unwrap(), ("file_gist", "file_token"));
+    assert_eq!(describe(&config), vec!["Last.fm (file_user)"]);
+    assert!(has_source(&config, "YouTube Music"));
+}
+
+#[test]
+fn env_and_flags_override_config_file() {
+    clear_env();
+    unsafe {
+        env::set_var("DAYS", "30");
+        env::set_var("LASTFM_USERNAME", "env_user");
+    }
+    let path = write_config(
+        "precedence",
+        r#"
+days = 14
+top_n = 3
+
+[lastfm]
+api_key = "key"
+username = "file_user"
+"#,
+    );
+
+    let overrides = Overrides {
+        top_n: Some(8),
+        ..with_file(path)
+    };
+
+    let config = load_with(&overrides).unwrap();
+    assert_eq!(config.days, 30);
+    assert_eq!(config.top_n, 8);
+    assert_eq!(describe(&config), vec!["Last.fm (env_user)"]);
+}
+
+#[test]
+fn finds_config_file_through_env_var() {
+    clear_env();
+    let path = write_config("env_var", "[youtube]\ncookie = \"cookie\"\n");
+    unsafe {
+        env::set_var("MUSIC_STATS_CONFIG", &path);
+    }
+
+    let config = load_with(&Overrides::default()).unwrap();
+    assert!(has_source(&config, "YouTube Music"));
+}
+
+#[test]
+fn finds_config_file_in_xdg_config_home() {
+    clear_env();
+    let home = env::temp_dir().join("music-stats-xdg");
+    std::fs::create_dir_all(home.join("music-stats")).unwrap();
+    std::fs::write(
+        home.join("music-stats").join("music-stats.toml"),
+        "[youtube]\ncookie = \"cookie\"\n",
+    )
+    .unwrap();
+    unsafe {
+        env::set_var("XDG_CONFIG_HOME", &home);
+    }
+
+    let config = load_with(&Overrides::default()).unwrap();
+    assert!(has_source(&config, "YouTube Music"));
+}
+
+#[test]
+fn rejects_unknown_option_in_config_file() {
+    clear_env();
+    let path = write_config("unknown", "[lastfm]\nusernme = \"typo\"\n");
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("lastfm.usernme"));
+    assert!(format!("{}", error).contains("unknown option"));
+}
+
+#[test]
+fn rejects_wrong_type_in_config_file() {
+    clear_env();
+    let path = write_config("wrong_type", "[youtube]\ncookie = 5\n");
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("youtube.cookie"));
+}
+
+#[test]
+fn rejects_malformed_config_file() {
+    clear_env();
+    let path = write_config("malformed", "days = = 7\n");
+
+    let result = load_with(&with_file(path));
+    assert!(matches!(result, Err(Error::InvalidConfig { .. })));
+}
+
+#[test]
+fn fails_when_explicit_config_file_is_missing() {
+    clear_env();
+
+    let result = load_with(&with_file(PathBuf::from("/nonexistent/music-stats.toml")));
+    assert!(matches!(result, Err(Error::Input { .. })));
+}
+
+#[test]
+fn loads_listenbrainz_provider() {
+    clear_env();
+    let path = write_config(
+        "listenbrainz",
+        r#"
+[listenbrainz]
+username = "someone"
+api_root = "http://localhost:8100"
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "ListenBrainz"));
+    assert!(!has_source(&config, "Last.fm"));
+}
+
+#[test]
+fn loads_spotify_export_paths() {
+    clear_env();
+    let path = write_config(
+        "spotify_export",
+        r#"
+[spotify_export]
+paths = ["a.json", "my_spotify_data.zip"]
+min_played_seconds = 30
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "Spotify export"));
+}
+
+#[test]
+fn rejects_non_path_list() {
+    clear_env();
+    let path = write_config("spotify_export_bad", "[spotify_export]\npaths = [1, 2]\n");
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("spotify_export.paths"));
+}
+
+#[test]
+fn loads_takeout_paths_from_env() {
+    clear_env();
+    unsafe {
+        env::set_var("YOUTUBE_TAKEOUT_PATHS", "takeout.zip:watch-history.json");
+    }
+
+    let config = load_with(&Overrides::default()).unwrap();
+    assert!(has_source(&config, "YouTube Takeout"));
+}
+
+#[test]
+fn loads_named_file_imports() {
+    clear_env();
+    let path = write_config(
+        "file_import",
+        r#"
+[file_import.scrobbler]
+paths = "plays.csv"
+artist = "Artist"
+title = "Track"
+timestamp = "Played At"
+timestamp_format = "%d/%m/%Y %H:%M"
+timezone = "America/Lima"
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "scrobbler"));
+}
+
+#[test]
+fn rejects_file_import_without_mapping() {
+    clear_env();
+    let path = write_config(
+        "file_import_bad",
+        "[file_import.scrobbler]\npaths = \"plays.csv\"\nartist = \"Artist\"\n",
+    );
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("file_import.scrobbler.title"));
+}
+
+#[test]
+fn rejects_unknown_time_zone() {
+    clear_env();
+    let path = write_config(
+        "file_import_tz",
+        r#"
+[file_import.scrobbler]
+paths = "plays.csv"
+artist = "a"
+title = "t"
+timestamp = "ts"
+timezone = "Mars/Olympus"
+"#,
+    );
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("file_import.scrobbler.timezone"));
+}
+
+#[test]
+fn loads_several_lastfm_compatible_services() {
+    clear_env();
+    let path = write_config(
+        "lastfm_instances",
+        r#"
+[lastfm]
+api_key = "key"
+username = "someone"
+
+[lastfm.librefm]
+name = "Libre.fm"
+api_key = "anything"
+username = "someone_else"
+api_root = "https://libre.fm/2.0/"
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "Last.fm"));
+    assert!(has_source(&config, "Libre.fm"));
+}
+
+#[test]
+fn rejects_lastfm_instance_without_username() {
+    clear_env();
+    let path = write_config(
+        "lastfm_instance_bad",
+        "[lastfm.librefm]\napi_key = \"anything\"\n",
+    );
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("lastfm.librefm.username"));
+}
+
+#[test]
+fn rejects_lastfm_instance_without_api_root() {
+    clear_env();
+    let path = write_config(
+        "lastfm_instance_no_root",
+        "[lastfm.librefm]\napi_key = \"anything\"\nusername = \"someone\"\n",
+    );
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("lastfm.librefm.api_root"));
+}
+
+#[test]
+fn loads_subsonic_servers() {
+    clear_env();
+    unsafe {
+        env::set_var("SUBSONIC_URL", "https://music.example.com");
+        env::set_var("SUBSONIC_USERNAME", "someone");
+        env::set_var("SUBSONIC_PASSWORD", "sesame");
+    }
+    let path = write_config(
+        "subsonic",
+        r#"
+[subsonic.gonic]
+url = "http://192.168.1.10:4747"
+username = "someone"
+password = "sesame"
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "Subsonic"));
+    assert!(has_source(&config, "gonic"));
+}
+
+#[test]
+fn loads_named_commands() {
+    clear_env();
+    let path = write_config(
+        "command",
+        r#"
+[command.cmus]
+program = "cmus-history"
+args = ["--json", "--all"]
+timeout_seconds = 10
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "cmus"));
+}
+
+#[test]
+fn rejects_command_without_program() {
+    clear_env();
+    let path = write_config("command_bad", "[command.cmus]\nargs = \"--json\"\n");
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("command.cmus.program"));
+}
+
+#[test]
+fn loads_sync_settings() {
+    clear_env();
+    let path = write_config(
+        "sync",
+        r#"
+[lastfm]
+api_key = "key"
+api_secret = "secret"
+session_key = "session"
+username = "someone"
+
+[youtube]
+cookie = "cookie"
+
+[sync]
+ledger = "synced.jsonl"
+tolerance_minutes = 90
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    let sync = config.sync.unwrap();
+    assert_eq!(sync.session.session_key, "session");
+    assert_eq!(sync.lastfm.session_key.as_deref(), Some("session"));
+    assert_eq!(sync.tolerance, chrono::Duration::minutes(90));
+}
+
+#[test]
+fn sync_needs_a_session_key() {
+    clear_env();
+    let path = write_config(
+        "sync_bad",
+        r#"
+[lastfm]
+api_key = "key"
+api_secret = "secret"
+username = "someone"
+
+[youtube]
+cookie = "cookie"
+
+[sync]
+ledger = "synced.jsonl"
+"#,
+    );
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("lastfm.session_key"));
+}
+
+#[test]
+fn stores_an_option_without_touching_the_rest_of_the_file() {
+    clear_env();
+    let path = write_config(
+        "store",
+        r#"# my settings
+days = 30
+
+[lastfm]
+api_key = "key"   # from last.fm/api
+username = "someone"
+"#,
+    );
+
+    let written = store(&with_file(path.clone()), "lastfm.session_key", "session").unwrap();
+    assert_eq!(written, path);
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.starts_with("# my settings\ndays = 30\n"));
+    assert!(content.contains(r#"api_key = "key"   # from last.fm/api"#));
+    assert!(content.contains(r#"session_key = "session""#));
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert_eq!(config.days, 30);
+}
+
+#[test]
+fn stores_into_a_new_section() {
+    clear_env();
+    let path = write_config("store_new", "days = 30\n");
+
+    store(&with_file(path.clone()), "lastfm.session_key", "session").unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        content,
+        "days = 30\n\n[lastfm]\nsession_key = \"session\"\n"
+    );
+}
+
+#[test]
+fn loads_chart_settings() {
+    clear_env();
+    let path = write_config(
+        "charts",
+        r#"
+[lastfm]
+api_key = "key"
+username = "someone"
+period = "12month"
+charts = ["artists", "albums"]
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    let charts = config.charts.unwrap();
+    assert_eq!(charts.period, Period::Year);
+    assert_eq!(charts.charts, vec![Chart::Artists, Chart::Albums]);
+    assert_eq!(charts.lastfm.username, "someone");
+}
+
+#[test]
+fn charts_default_to_top_tracks() {
+    clear_env();
+    unsafe {
+        env::set_var("LASTFM_API_KEY", "key");
+        env::set_var("LASTFM_USERNAME", "someone");
+        env::set_var("LASTFM_PERIOD", "overall");
+    }
+
+    let config = load_with(&Overrides::default()).unwrap();
+    let charts = config.charts.unwrap();
+    assert_eq!(charts.period, Period::Overall);
+    assert_eq!(charts.charts, vec![Chart::Tracks]);
+}
+
+#[test]
+fn rejects_unknown_chart_period() {
+    clear_env();
+    let path = write_config(
+        "charts_bad",
+        r#"
+[lastfm]
+api_key = "key"
+username = "someone"
+period = "2year"
+"#,
+    );
+
+    let error = load_with(&with_file(path)).unwrap_err();
+    assert!(format!("{}", error).contains("lastfm.period"));
+}
+
+#[test]
+fn names_the_file_for_zero_days_set_there() {
+    clear_env();
+    let path = write_config(
+        "zero_days",
+        r#"
+days = 0
+
+[youtube]
+cookie = "cookie"
+"#,
+    );
+
+    let error = load_with(&with_file(path.clone())).unwrap_err();
+    let message = format!("{}", error);
+    assert!(message.contains(&format!("days in {}", path.display())));
+    assert!(!message.contains("DAYS"));
+}
+
+#[test]
+fn accepts_a_huge_spotify_export_threshold() {
+    clear_env();
+    let path = write_config(
+        "spotify_export_threshold",
+        r#"
+[spotify_export]
+paths = ["/tmp/StreamingHistory.json"]
+min_played_seconds = 9223372036854775807
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "Spotify export"));
+}
+
+#[test]
+fn accepts_a_huge_apple_music_export_threshold() {
+    clear_env();
+    let path = write_config(
+        "apple_music_export_threshold",
+        r#"
+[apple_music_export]
+paths = ["/tmp/Apple Music Play Activity.csv"]
+min_played_seconds = 9223372036854775807
+"#,
+    );
+
+    let config = load_with(&with_file(path)).unwrap();
+    assert!(has_source(&config, "Apple Music export"));
 }
@@ -0,0 +1,153 @@
+use chrono::{TimeZone, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::deezer::{DeezerConfig, fetch_scrobbles};
+use music_stats::providers::source::TimeWindow;
+use serde_json::{Value, json};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn config(server: &MockServer) -> DeezerConfig {
+    DeezerConfig {
+        access_token: "secret".into(),
+        api_root: server.uri(),
+    }
+}
+
+fn window(from: i64, to: i64) -> TimeWindow {
+    TimeWindow {
+        from: Utc.timestamp_opt(from, 0).unwrap(),
+        to: Utc.timestamp_opt(to, 0).unwrap(),
+    }
+}
+
+fn entry(timestamp: i64, title: &str) -> Value {
+    json!({
+        "id": 3135556,
+        "title": title,
+        "title_short": title,
+        "duration": 284,
+        "timestamp": timestamp,
+        "artist": {"id": 399, "name": "Radiohead", "type": "artist"},
+        "album": {"id": 302127, "title": "OK Computer", "type": "album"},
+        "type": "track"
+    })
+}
+
+#[tokio::test]
+async fn follows_next_until_past_the_window() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/user/me/history"))
+        .and(query_param("access_token", "secret"))
+        .and(query_param("index", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [entry(1500, "Lucky"), entry(900, "Too old")],
+            "total": 5,
+            "next": format!("{}/user/me/history?access_token=secret&index=4", server.uri())
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/user/me/history"))
+        .and(query_param("access_token", "secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [entry(2100, "Too new"), entry(1800, "Airbag")],
+            "total": 5,
+            "next": format!("{}/user/me/history?access_token=secret&index=2", server.uri())
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(1000, 2000),
+    )
+    .await
+    .unwrap();
+
+    let titles: Vec<&str> = scrobbles.iter().map(|s| s.track.title.as_str()).collect();
+    assert_eq!(titles, vec!["Airbag", "Lucky"]);
+    assert_eq!(scrobbles[0].track.artist, "Radiohead");
+    assert_eq!(scrobbles[0].album.as_deref(), Some("OK Computer"));
+}
+
+#[tokio::test]
+async fn encodes_the_access_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/user/me/history"))
+        .and(query_param("access_token", "a+b&c=d"))
+        .and(query_param("limit", "100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [entry(1500, "Lucky")]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = DeezerConfig {
+        access_token: "a+b&c=d".into(),
+        ..config(&server)
+    };
+    let scrobbles = fetch_scrobbles(&reqwest::Client::new(), &config, window(1000, 2000))
+        .await
+        .unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+}
+
+#[tokio::test]
+async fn reports_api_errors_without_the_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/user/me/history"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "error": {"type": "OAuthException", "message": "Invalid OAuth access token.", "code": 300}
+        })))
+        .mount(&server)
+        .await;
+
+    let error = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(1000, 2000),
+    )
+    .await
+    .unwrap_err();
+
+    match error {
+        Error::Deezer { status, url, body } => {
+            assert_eq!(status, 200);
+            assert!(!url.contains("secret"));
+            assert!(body.contains("Invalid OAuth access token"));
+        }
+        other => panic!("expected a Deezer error, got {}", other),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn stops_at_the_page_cap() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/user/me/history"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [entry(1500, "Lucky")],
+            "next": format!("{}/user/me/history?access_token=secret&index=1", server.uri())
+        })))
+        .expect(50)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(1000, 2000),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 50);
+}
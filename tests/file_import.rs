@@ -0,0 +1,172 @@
+use chrono_tz::Tz;
+use music_stats::errors::Error;
+use music_stats::providers::file_import::{
+    FieldMapping, FileImportConfig, TimestampFormat, parse_csv, parse_json_lines, read_files,
+};
+
+const PLAYS_CSV: &str = "\
+played,artist,song,record
+2024-01-15 12:34:00,Radiohead,Airbag,OK Computer
+2024-01-15 13:00:00,,Untitled,
+yesterday,Portishead,Roads,Dummy
+2024-01-15 14:00:00,Portishead,Glory Box,
+";
+
+const PLAYS_JSONL: &str = r#"{"ts": 1705322040000, "track": {"artist": "Radiohead", "name": "Airbag"}}
+{"ts": 1705323000000, "track": {"name": "No artist"}}
+not json
+
+{"ts": 1705324000000, "track": {"artist": "Portishead", "name": "Roads", "album": "Dummy"}}
+"#;
+
+fn csv_mapping() -> FieldMapping {
+    FieldMapping {
+        artist: "artist".to_string(),
+        title: "song".to_string(),
+        album: Some("record".to_string()),
+        timestamp: "played".to_string(),
+        timestamp_format: TimestampFormat::Pattern("%Y-%m-%d %H:%M:%S".to_string()),
+        timezone: "Europe/Berlin".parse::<Tz>().unwrap(),
+    }
+}
+
+fn json_mapping() -> FieldMapping {
+    FieldMapping {
+        artist: "track.artist".to_string(),
+        title: "track.name".to_string(),
+        album: Some("track.album".to_string()),
+        timestamp: "ts".to_string(),
+        timestamp_format: TimestampFormat::UnixMillis,
+        timezone: Tz::UTC,
+    }
+}
+
+#[test]
+fn maps_csv_columns_and_applies_timezone() {
+    let report = parse_csv(PLAYS_CSV, true, b',', &csv_mapping()).unwrap();
+
+    assert_eq!(report.scrobbles.len(), 2);
+    assert_eq!(report.scrobbles[0].track.artist, "Radiohead");
+    assert_eq!(report.scrobbles[0].track.title, "Airbag");
+    assert_eq!(report.scrobbles[0].album.as_deref(), Some("OK Computer"));
+    // 12:34 in Berlin is 11:34 UTC in January.
+    assert_eq!(report.scrobbles[0].played_at.timestamp(), 1705318440);
+    assert_eq!(report.scrobbles[1].album, None);
+
+    assert_eq!(report.skipped.get("missing artist"), Some(&1));
+    assert_eq!(report.skipped.get("bad timestamp"), Some(&1));
+    assert_eq!(
+        report.describe_skipped(),
+        "1 bad timestamp, 1 missing artist"
+    );
+}
+
+#[test]
+fn maps_columns_by_index_without_header() {
+    let mapping = FieldMapping {
+        artist: "1".to_string(),
+        title: "0".to_string(),
+        album: None,
+        timestamp: "2".to_string(),
+        timestamp_format: TimestampFormat::Unix,
+        timezone: Tz::UTC,
+    };
+
+    let report = parse_csv("Airbag;Radiohead;1705322040\n", false, b';', &mapping).unwrap();
+
+    assert_eq!(report.scrobbles.len(), 1);
+    assert_eq!(report.scrobbles[0].track.artist, "Radiohead");
+    assert_eq!(report.scrobbles[0].played_at.timestamp(), 1705322040);
+}
+
+#[test]
+fn rejects_csv_without_mapped_column() {
+    let error = parse_csv(
+        "artist,title\nRadiohead,Airbag\n",
+        true,
+        b',',
+        &csv_mapping(),
+    )
+    .unwrap_err();
+
+    assert!(error.contains("song"));
+}
+
+#[test]
+fn maps_nested_json_fields() {
+    let report = parse_json_lines(PLAYS_JSONL, &json_mapping());
+
+    assert_eq!(report.scrobbles.len(), 2);
+    assert_eq!(report.scrobbles[0].track.title, "Airbag");
+    assert_eq!(report.scrobbles[0].played_at.timestamp(), 1705322040);
+    assert_eq!(report.scrobbles[1].album.as_deref(), Some("Dummy"));
+    assert_eq!(report.skipped_total(), 2);
+    assert_eq!(report.skipped.get("invalid JSON"), Some(&1));
+}
+
+#[test]
+fn reads_files_by_extension() {
+    let dir = std::env::temp_dir().join("music-stats-file-import");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("plays.jsonl"), PLAYS_JSONL).unwrap();
+    std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+    let config = FileImportConfig {
+        name: "scrobbler".to_string(),
+        paths: vec![dir.clone()],
+        format: None,
+        has_header: true,
+        delimiter: None,
+        mapping: json_mapping(),
+    };
+    let report = read_files(&config).unwrap();
+
+    assert_eq!(report.scrobbles.len(), 2);
+}
+
+#[test]
+fn reads_tsv_files_with_tabs_by_default() {
+    let path = std::env::temp_dir().join("music-stats-file-import-plays.tsv");
+    std::fs::write(&path, PLAYS_CSV.replace(',', "\t")).unwrap();
+
+    let config = FileImportConfig {
+        name: "scrobbler".to_string(),
+        paths: vec![path],
+        format: None,
+        has_header: true,
+        delimiter: None,
+        mapping: csv_mapping(),
+    };
+    let report = read_files(&config).unwrap();
+
+    assert_eq!(report.scrobbles.len(), 2);
+    assert_eq!(report.scrobbles[0].track.title, "Airbag");
+    assert_eq!(report.scrobbles[0].album.as_deref(), Some("OK Computer"));
+}
+
+#[test]
+fn reports_malformed_csv_as_input_error() {
+    let path = std::env::temp_dir().join("music-stats-file-import-bad.csv");
+    std::fs::write(&path, "artist,song\nRadiohead,Airbag\n").unwrap();
+
+    let config = FileImportConfig {
+        name: "broken".to_string(),
+        paths: vec![path],
+        format: None,
+        has_header: true,
+        delimiter: None,
+        mapping: csv_mapping(),
+    };
+
+    match read_files(&config) {
+        Err(Error::Input { path, detail }) => {
+            assert!(path.ends_with("music-stats-file-import-bad.csv"));
+            assert!(detail.contains("played"));
+        }
+        other => panic!(
+            "expected an input error, got {:?}",
+            other.map(|r| r.scrobbles)
+        ),
+    }
+}
@@ -1,13 +1,16 @@
 use music_stats::aggregate::Statistics;
 use music_stats::output::format::format_statistics;
-use music_stats::providers::types::Track;
+use music_stats::providers::types::{Album, Track};
 
 #[test]
 fn empty_statistics() {
     let stats = Statistics {
         top_tracks: vec![],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 0,
         unique_tracks: 0,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -24,8 +27,11 @@ fn single_track_single_play() {
             },
             1,
         )],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 1,
         unique_tracks: 1,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -44,8 +50,11 @@ fn single_track_multiple_plays() {
             },
             5,
         )],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 5,
         unique_tracks: 1,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -71,8 +80,11 @@ fn multiple_tracks_formatted_as_lines() {
                 2,
             ),
         ],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 5,
         unique_tracks: 2,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -94,8 +106,11 @@ fn long_title_truncated_with_ellipsis() {
             },
             1,
         )],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 1,
         unique_tracks: 1,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -114,8 +129,11 @@ fn long_artist_truncated_with_ellipsis() {
             },
             1,
         )],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 1,
         unique_tracks: 1,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -132,8 +150,11 @@ fn unicode_characters_handled() {
             },
             2,
         )],
+        top_artists: vec![],
+        top_albums: vec![],
         total_plays: 2,
         unique_tracks: 1,
+        now_playing: None,
     };
 
     let output = format_statistics(&stats);
@@ -141,3 +162,108 @@ fn unicode_characters_handled() {
     assert!(output.contains("タイトル"));
     assert!(output.contains("(2×)"));
 }
+
+#[test]
+fn now_playing_shown_above_top_list() {
+    let stats = Statistics {
+        top_tracks: vec![(
+            Track {
+                artist: "Artist".into(),
+                title: "Title".into(),
+            },
+            3,
+        )],
+        top_artists: vec![],
+        top_albums: vec![],
+        total_plays: 3,
+        unique_tracks: 1,
+        now_playing: Some(Track {
+            artist: "Radiohead".into(),
+            title: "Reckoner".into(),
+        }),
+    };
+
+    let output = format_statistics(&stats);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "▶ Now playing: Reckoner - Radiohead");
+    assert!(lines[1].starts_with("Title"));
+}
+
+#[test]
+fn now_playing_shown_without_recent_plays() {
+    let stats = Statistics {
+        top_tracks: vec![],
+        top_artists: vec![],
+        top_albums: vec![],
+        total_plays: 0,
+        unique_tracks: 0,
+        now_playing: Some(Track {
+            artist: "Radiohead".into(),
+            title: "Reckoner".into(),
+        }),
+    };
+
+    let output = format_statistics(&stats);
+    assert_eq!(
+        output,
+        "▶ Now playing: Reckoner - Radiohead\nNo tracks played recently"
+    );
+}
+
+#[test]
+fn artists_and_albums_follow_the_tracks() {
+    let stats = Statistics {
+        top_tracks: vec![(
+            Track {
+                artist: "Radiohead".into(),
+                title: "Reckoner".into(),
+            },
+            12,
+        )],
+        top_artists: vec![("Radiohead".into(), 340), ("Björk".into(), 1)],
+        top_albums: vec![(
+            Album {
+                artist: "Radiohead".into(),
+                title: "In Rainbows".into(),
+            },
+            80,
+        )],
+        total_plays: 12,
+        unique_tracks: 1,
+        now_playing: None,
+    };
+
+    let output = format_statistics(&stats);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 9);
+    assert_eq!(lines[0], "Top tracks");
+    assert!(lines[1].starts_with("Reckoner"));
+    assert_eq!(lines[2], "");
+    assert_eq!(lines[3], "Top artists");
+    assert_eq!(lines[4], format!("Radiohead{}(340×)", " ".repeat(31)));
+    assert_eq!(lines[5], "Björk");
+    assert_eq!(lines[6], "");
+    assert_eq!(lines[7], "Top albums");
+    assert!(lines[8].starts_with("In Rainbows"));
+    assert!(lines[8].ends_with("Radiohead (80×)"));
+    // Artist counts start in the same column as the track artists.
+    assert_eq!(lines[4].find('('), lines[1].find("Radiohead"));
+}
+
+#[test]
+fn artists_alone_are_not_reported_as_empty() {
+    let stats = Statistics {
+        top_tracks: vec![],
+        top_artists: vec![("Radiohead".into(), 340)],
+        top_albums: vec![],
+        total_plays: 0,
+        unique_tracks: 0,
+        now_playing: None,
+    };
+
+    assert_eq!(
+        format_statistics(&stats),
+        format!("Top artists\nRadiohead{}(340×)", " ".repeat(31))
+    );
+}
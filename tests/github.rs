@@ -0,0 +1,13 @@
+use music_stats::output::github::render_payload;
+
+#[test]
+fn payload_contains_content_under_gist_file() {
+    let payload: serde_json::Value =
+        serde_json::from_str(&render_payload("Title    Artist (2×)")).unwrap();
+
+    assert!(payload["description"].is_string());
+    let files = payload["files"].as_object().unwrap();
+    assert_eq!(files.len(), 1);
+    let file = files.values().next().unwrap();
+    assert_eq!(file["content"], "Title    Artist (2×)");
+}
@@ -1,4 +1,11 @@
+use chrono::{TimeZone, Utc};
+use music_stats::errors::{Error, LastFmErrorCode};
+use music_stats::providers::lastfm::{LastFmConfig, LastFmSource, fetch_history, fetch_scrobbles};
+use music_stats::providers::lastfm_signed::sign;
+use music_stats::providers::source::{ScrobbleSource, TimeWindow};
 use serde_json::json;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate, Times};
 
 #[test]
 fn parses_real_lastfm_history() {
@@ -50,3 +57,526 @@ fn parses_lastfm_minimal_structure() {
     assert_eq!(tracks[0]["name"], "Song");
     assert_eq!(tracks[0]["artist"]["#text"], "Artist");
 }
+
+#[tokio::test]
+async fn fetches_from_configured_api_root() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/2.0/"))
+        .and(query_param("method", "user.getrecenttracks"))
+        .and(query_param("user", "someone"))
+        .and(query_param("from", "1000"))
+        .and(query_param("to", "2000"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [
+                    {"name": "Playing", "artist": {"#text": "Artist"}},
+                    {"name": "Song", "artist": {"#text": "Artist"}, "date": {"uts": "1500"}}
+                ],
+                "@attr": {"totalPages": "1"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = LastFmConfig {
+        name: "Libre.fm".into(),
+        api_key: "anything".into(),
+        username: "someone".into(),
+        api_root: format!("{}/2.0/", server.uri()),
+        max_pages: None,
+        api_secret: None,
+        session_key: None,
+    };
+    let window = TimeWindow {
+        from: Utc.timestamp_opt(1000, 0).unwrap(),
+        to: Utc.timestamp_opt(2000, 0).unwrap(),
+    };
+
+    let scrobbles = fetch_scrobbles(&reqwest::Client::new(), &config, window)
+        .await
+        .unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.title, "Song");
+}
+
+fn page_response(page: usize, total_pages: usize) -> serde_json::Value {
+    let tracks: Vec<serde_json::Value> = (0..200)
+        .map(|i| {
+            let uts = 100_000 - (page * 200 + i);
+            json!({
+                "name": format!("Song {}", page * 200 + i),
+                "artist": {"#text": "Artist"},
+                "date": {"uts": uts.to_string()}
+            })
+        })
+        .collect();
+    json!({
+        "recenttracks": {
+            "track": tracks,
+            "@attr": {"page": page.to_string(), "totalPages": total_pages.to_string()}
+        }
+    })
+}
+
+async fn mount_pages<T: Into<Times>>(
+    server: &MockServer,
+    total_pages: usize,
+    expected: impl Fn(usize) -> T,
+) {
+    for page in 1..=total_pages {
+        Mock::given(method("GET"))
+            .and(query_param("page", page.to_string()))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(page_response(page, total_pages)),
+            )
+            .expect(expected(page))
+            .mount(server)
+            .await;
+    }
+}
+
+fn paged_config(server: &MockServer, max_pages: Option<usize>) -> LastFmConfig {
+    LastFmConfig {
+        name: "Last.fm".into(),
+        api_key: "key".into(),
+        username: "someone".into(),
+        api_root: server.uri(),
+        max_pages,
+        api_secret: None,
+        session_key: None,
+    }
+}
+
+fn wide_window() -> TimeWindow {
+    TimeWindow {
+        from: Utc.timestamp_opt(0, 0).unwrap(),
+        to: Utc.timestamp_opt(200_000, 0).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn fetches_every_page_in_order() {
+    let server = MockServer::start().await;
+    mount_pages(&server, 12, |_| 1).await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 12 * 200);
+    assert!(
+        scrobbles
+            .windows(2)
+            .all(|pair| pair[0].played_at > pair[1].played_at)
+    );
+}
+
+#[tokio::test]
+async fn stops_at_the_configured_page_cap() {
+    let server = MockServer::start().await;
+    mount_pages(&server, 5, |page| if page <= 2 { 1 } else { 0 }).await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, Some(2)),
+        wide_window(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 400);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_when_any_page_fails() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("page", "3"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("Operation failed"))
+        .mount(&server)
+        .await;
+    mount_pages(&server, 4, |_| 0..=1).await;
+
+    let result = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn keeps_plays_repeated_across_pages_once() {
+    let server = MockServer::start().await;
+    let track = |name: &str, uts: &str| json!({"name": name, "artist": {"#text": "Artist"}, "date": {"uts": uts}});
+    Mock::given(method("GET"))
+        .and(query_param("to", "2000"))
+        .and(query_param("page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [track("C", "1900"), track("B", "1800")],
+                "@attr": {"totalPages": "2"}
+            }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(query_param("to", "2000"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [track("B", "1800"), track("A", "1700"), track("B", "1600")],
+                "@attr": {"totalPages": "2"}
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let window = TimeWindow {
+        from: Utc.timestamp_opt(1000, 0).unwrap(),
+        to: Utc.timestamp_opt(2000, 0).unwrap(),
+    };
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        window,
+    )
+    .await
+    .unwrap();
+
+    let titles: Vec<&str> = scrobbles.iter().map(|s| s.track.title.as_str()).collect();
+    assert_eq!(titles, vec!["C", "B", "A", "B"]);
+}
+
+fn single_page() -> serde_json::Value {
+    json!({
+        "recenttracks": {
+            "track": [{"name": "Song", "artist": {"#text": "Artist"}, "date": {"uts": "1500"}}],
+            "@attr": {"totalPages": "1"}
+        }
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_when_rate_limited() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "error": 29,
+            "message": "Rate Limit Exceeded"
+        })))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_page()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_internal_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("Operation failed"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_page()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn gives_up_after_repeated_failures() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(json!({
+            "error": 16,
+            "message": "There was a temporary error processing your request."
+        })))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    let error = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(matches!(
+        error,
+        Error::LastFmApi {
+            code: LastFmErrorCode::TemporarilyUnavailable,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn explains_errors_that_are_not_worth_retrying() {
+    let cases = [
+        (
+            10,
+            "Invalid API key - You must be granted a valid key by last.fm",
+            "lastfm.api_key",
+        ),
+        (6, "User not found", "lastfm.username"),
+    ];
+
+    for (code, message, hint) in cases {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .set_body_json(json!({"error": code, "message": message})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let error = fetch_scrobbles(
+            &reqwest::Client::new(),
+            &paged_config(&server, None),
+            wide_window(),
+        )
+        .await
+        .unwrap_err();
+
+        let text = format!("{}", error);
+        assert!(text.contains(message), "{}", text);
+        assert!(text.contains(hint), "{}", text);
+        assert!(!text.contains("key=key"), "{}", text);
+    }
+}
+
+#[test]
+fn maps_lastfm_error_codes() {
+    assert_eq!(
+        LastFmErrorCode::from_code(26),
+        LastFmErrorCode::InvalidApiKey
+    );
+    assert_eq!(LastFmErrorCode::from_code(29), LastFmErrorCode::RateLimited);
+    assert_eq!(
+        LastFmErrorCode::from_code(11),
+        LastFmErrorCode::TemporarilyUnavailable
+    );
+    assert_eq!(LastFmErrorCode::from_code(13), LastFmErrorCode::Other(13));
+    assert!(LastFmErrorCode::from_code(8).is_retryable());
+    assert!(!LastFmErrorCode::from_code(10).is_retryable());
+}
+
+#[tokio::test]
+async fn keeps_the_now_playing_entry_from_the_first_page() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [
+                    {
+                        "name": "Reckoner",
+                        "artist": {"#text": "Radiohead"},
+                        "@attr": {"nowplaying": "true"}
+                    },
+                    {"name": "Nude", "artist": {"#text": "Radiohead"}, "date": {"uts": "1500"}}
+                ],
+                "@attr": {"totalPages": "1"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let source = LastFmSource::new(paged_config(&server, None));
+    let client = reqwest::Client::new();
+    let scrobbles = source.fetch(&client, wide_window()).await.unwrap();
+    let track = source.now_playing(&client).await.unwrap().unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.title, "Nude");
+    assert_eq!(track.title, "Reckoner");
+    assert_eq!(track.artist, "Radiohead");
+}
+
+#[tokio::test]
+async fn asks_what_is_playing_when_nothing_was_fetched() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.getrecenttracks"))
+        .and(query_param("limit", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [{
+                    "name": "Reckoner",
+                    "artist": {"#text": "Radiohead"},
+                    "@attr": {"nowplaying": "true"}
+                }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let source = LastFmSource::new(paged_config(&server, None));
+    let track = source
+        .now_playing(&reqwest::Client::new())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(track.title, "Reckoner");
+}
+
+#[tokio::test]
+async fn nothing_playing_without_the_marker() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_page()))
+        .mount(&server)
+        .await;
+
+    let history = fetch_history(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(history.scrobbles.len(), 1);
+    assert_eq!(history.now_playing, None);
+}
+
+#[tokio::test]
+async fn carries_extended_metadata() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("extended", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [{
+                    "name": "Reckoner",
+                    "mbid": "b9b3b8a5-1a56-4a9c-9a2b-9f0d1ad5b6c1",
+                    "loved": "1",
+                    "artist": {
+                        "name": "Radiohead",
+                        "mbid": "a74b1b7f-71a5-4011-9441-d0b5e4122711",
+                        "url": "https://www.last.fm/music/Radiohead",
+                        "image": []
+                    },
+                    "album": {
+                        "#text": "In Rainbows",
+                        "mbid": ""
+                    },
+                    "image": [
+                        {"size": "small", "#text": "https://lastfm.freetls.fastly.net/i/u/34s/a.png"},
+                        {"size": "extralarge", "#text": "https://lastfm.freetls.fastly.net/i/u/300x300/a.png"},
+                        {"size": "mega", "#text": ""}
+                    ],
+                    "date": {"uts": "1500", "#text": "01 Jan 1970, 00:25"}
+                }],
+                "@attr": {"totalPages": "1"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &paged_config(&server, None),
+        wide_window(),
+    )
+    .await
+    .unwrap();
+
+    let scrobble = &scrobbles[0];
+    assert_eq!(scrobble.track.artist, "Radiohead");
+    assert_eq!(scrobble.album.as_deref(), Some("In Rainbows"));
+    assert_eq!(
+        scrobble.metadata.track_mbid.as_deref(),
+        Some("b9b3b8a5-1a56-4a9c-9a2b-9f0d1ad5b6c1")
+    );
+    assert_eq!(
+        scrobble.metadata.artist_mbid.as_deref(),
+        Some("a74b1b7f-71a5-4011-9441-d0b5e4122711")
+    );
+    assert_eq!(scrobble.metadata.album_mbid, None);
+    assert_eq!(scrobble.metadata.loved, Some(true));
+    let sizes: Vec<&str> = scrobble
+        .metadata
+        .artwork
+        .iter()
+        .map(|art| art.size.as_str())
+        .collect();
+    assert_eq!(sizes, vec!["small", "extralarge"]);
+}
+
+#[tokio::test]
+async fn signs_requests_when_a_session_is_configured() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("sk", "session"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_page()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = LastFmConfig {
+        api_secret: Some("secret".into()),
+        session_key: Some("session".into()),
+        ..paged_config(&server, None)
+    };
+    fetch_scrobbles(&reqwest::Client::new(), &config, wide_window())
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let params: Vec<(String, String)> = requests[0]
+        .url
+        .query_pairs()
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    let (signed, signature): (Vec<_>, Vec<_>) =
+        params.into_iter().partition(|(name, _)| name != "api_sig");
+    assert_eq!(signature.len(), 1);
+    assert_eq!(signature[0].1, sign(&signed, "secret"));
+}
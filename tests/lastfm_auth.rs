@@ -0,0 +1,101 @@
+use music_stats::providers::lastfm_signed::{App, auth_url, get_token, wait_for_session};
+use serde_json::json;
+use std::time::Duration;
+use wiremock::matchers::{body_string_contains, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn app(api_root: &str) -> App {
+    App {
+        api_key: "key".into(),
+        api_secret: "secret".into(),
+        api_root: api_root.into(),
+    }
+}
+
+#[test]
+fn sends_lastfm_users_to_the_website() {
+    let url = auth_url(&app("https://ws.audioscrobbler.com/2.0"), "tok");
+    assert_eq!(url, "https://www.last.fm/api/auth/?api_key=key&token=tok");
+}
+
+#[test]
+fn sends_gnu_fm_users_to_their_server() {
+    let url = auth_url(&app("https://libre.fm/2.0/"), "tok");
+    assert_eq!(url, "https://libre.fm/api/auth/?api_key=key&token=tok");
+}
+
+#[tokio::test]
+async fn requests_a_signed_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("method=auth.getToken"))
+        .and(body_string_contains("api_sig="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"token": "tok"})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let token = get_token(&reqwest::Client::new(), &app(&server.uri()))
+        .await
+        .unwrap();
+
+    assert_eq!(token, "tok");
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_until_the_token_is_approved() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("method=auth.getSession"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "error": 14,
+            "message": "Unauthorized Token - This token has not been authorized"
+        })))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("token=tok"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "session": {"name": "someone", "key": "session", "subscriber": 0}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let session_key = wait_for_session(
+        &reqwest::Client::new(),
+        &app(&server.uri()),
+        "tok",
+        Duration::from_secs(3),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(session_key, "session");
+}
+
+#[tokio::test]
+async fn reports_other_errors_while_waiting() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "error": 15,
+            "message": "This token has expired"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = wait_for_session(
+        &reqwest::Client::new(),
+        &app(&server.uri()),
+        "tok",
+        Duration::from_secs(3),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(error.to_string().contains("expired"));
+}
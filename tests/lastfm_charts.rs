@@ -0,0 +1,191 @@
+use music_stats::config::{Overrides, load_with};
+use music_stats::output::format::format_statistics;
+use music_stats::providers::lastfm::LastFmConfig;
+use music_stats::providers::lastfm_charts::{Chart, ChartsConfig, Period, fetch_statistics};
+use serde_json::json;
+use wiremock::matchers::{method, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn config(server: &MockServer, charts: Vec<Chart>) -> ChartsConfig {
+    ChartsConfig {
+        lastfm: LastFmConfig {
+            name: "Last.fm".into(),
+            api_key: "key".into(),
+            username: "someone".into(),
+            api_root: server.uri(),
+            max_pages: None,
+            api_secret: None,
+            session_key: None,
+        },
+        period: Period::Year,
+        charts,
+    }
+}
+
+#[tokio::test]
+async fn maps_top_tracks_into_statistics() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.gettoptracks"))
+        .and(query_param("user", "someone"))
+        .and(query_param("period", "12month"))
+        .and(query_param("limit", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "toptracks": {
+                "track": [
+                    {"name": "Reckoner", "playcount": "30", "artist": {"name": "Radiohead"}},
+                    {"name": "Hyperballad", "playcount": "12", "artist": {"name": "Björk"}}
+                ],
+                "@attr": {"user": "someone", "page": "1", "total": "517"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let stats = fetch_statistics(
+        &reqwest::Client::new(),
+        &config(&server, vec![Chart::Tracks]),
+        2,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(stats.top_tracks.len(), 2);
+    assert_eq!(stats.top_tracks[0].0.title, "Reckoner");
+    assert_eq!(stats.top_tracks[0].0.artist, "Radiohead");
+    assert_eq!(stats.top_tracks[0].1, 30);
+    assert_eq!(stats.total_plays, 42);
+    assert_eq!(stats.unique_tracks, 517);
+    assert!(stats.top_artists.is_empty());
+    assert!(stats.top_albums.is_empty());
+}
+
+#[tokio::test]
+async fn maps_top_artists_and_albums() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.gettopartists"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "topartists": {
+                "artist": [{"name": "Radiohead", "playcount": "340"}],
+                "@attr": {"total": "88"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.gettopalbums"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "topalbums": {
+                "album": [
+                    {"name": "In Rainbows", "playcount": "80", "artist": {"name": "Radiohead"}}
+                ],
+                "@attr": {"total": "120"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let stats = fetch_statistics(
+        &reqwest::Client::new(),
+        &config(&server, vec![Chart::Artists, Chart::Albums]),
+        5,
+    )
+    .await
+    .unwrap();
+
+    assert!(stats.top_tracks.is_empty());
+    assert_eq!(stats.top_artists, vec![("Radiohead".to_string(), 340)]);
+    assert_eq!(stats.top_albums.len(), 1);
+    assert_eq!(stats.top_albums[0].0.title, "In Rainbows");
+    assert_eq!(stats.top_albums[0].0.artist, "Radiohead");
+    assert_eq!(stats.top_albums[0].1, 80);
+}
+
+#[tokio::test]
+async fn keeps_at_most_top_n_entries() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "topartists": {
+                "artist": [
+                    {"name": "A", "playcount": "3"},
+                    {"name": "B", "playcount": "2"},
+                    {"name": "C", "playcount": "1"}
+                ]
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let stats = fetch_statistics(
+        &reqwest::Client::new(),
+        &config(&server, vec![Chart::Artists]),
+        2,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(stats.top_artists.len(), 2);
+}
+
+#[tokio::test]
+async fn shows_what_is_playing_next_to_the_charts() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.gettoptracks"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "toptracks": {
+                "track": [{"name": "Hyperballad", "playcount": "12", "artist": {"name": "Björk"}}],
+                "@attr": {"total": "1"}
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.getrecenttracks"))
+        .and(query_param("limit", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {
+                "track": [{
+                    "name": "Reckoner",
+                    "artist": {"#text": "Radiohead"},
+                    "@attr": {"nowplaying": "true"}
+                }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let path = std::env::temp_dir().join("music-stats-charts-now-playing.toml");
+    std::fs::write(
+        &path,
+        format!(
+            "[lastfm]\napi_key = \"key\"\nusername = \"someone\"\napi_root = \"{}\"\nperiod = \"7day\"\n",
+            server.uri()
+        ),
+    )
+    .unwrap();
+    let config = load_with(&Overrides {
+        config_path: Some(path),
+        ..Overrides::default()
+    })
+    .unwrap();
+
+    let client = reqwest::Client::new();
+    let mut stats = fetch_statistics(&client, config.charts.as_ref().unwrap(), config.top_n)
+        .await
+        .unwrap();
+    stats.now_playing = config.sources.now_playing(&client).await;
+    let output = format_statistics(&stats);
+
+    assert_eq!(
+        output.lines().next(),
+        Some("▶ Now playing: Reckoner - Radiohead")
+    );
+}
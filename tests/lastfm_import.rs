@@ -0,0 +1,81 @@
+use music_stats::providers::lastfm_import::{
+    LastFmImportConfig, parse_csv, parse_scrobbler_log, read_scrobbles,
+};
+
+const EXPORT_CSV: &str = "\
+Radiohead,OK Computer,Airbag,15 Jan 2024 12:34
+\"Crosby, Stills & Nash\",CSN,Helplessly Hoping,15 Jan 2024 13:00
+Now Playing,Album,Current Track,
+";
+
+const HEADED_CSV: &str = "\
+uts,utc_time,artist,artist_mbid,album,album_mbid,track,track_mbid
+1705322040,\"15 Jan 2024, 12:34\",Radiohead,,OK Computer,,Airbag,
+";
+
+const SCROBBLER_LOG: &str = "\
+#AUDIOSCROBBLER/1.1
+#TZ/UTC
+#CLIENT/Rockbox ipodvideo $Revision$
+Radiohead\tOK Computer\tAirbag\t1\t284\tL\t1705322040\t
+Radiohead\tOK Computer\tParanoid Android\t2\t383\tS\t1705322400\t
+Portishead\tDummy\tRoads\t\t305\tL\t1705323000\tabc-mbid
+";
+
+#[test]
+fn parses_headerless_csv_export() {
+    let scrobbles = parse_csv(EXPORT_CSV).unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.artist, "Radiohead");
+    assert_eq!(scrobbles[0].track.title, "Airbag");
+    assert_eq!(scrobbles[0].played_at.timestamp(), 1705322040);
+    assert_eq!(scrobbles[1].track.artist, "Crosby, Stills & Nash");
+}
+
+#[test]
+fn parses_csv_with_header() {
+    let scrobbles = parse_csv(HEADED_CSV).unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.title, "Airbag");
+    assert_eq!(scrobbles[0].played_at.timestamp(), 1705322040);
+}
+
+#[test]
+fn keeps_only_listened_tracks_from_log() {
+    let scrobbles = parse_scrobbler_log(SCROBBLER_LOG);
+
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.title, "Airbag");
+    assert_eq!(scrobbles[0].played_at.timestamp(), 1705322040);
+    assert_eq!(scrobbles[1].track.artist, "Portishead");
+}
+
+#[test]
+fn reads_csv_and_log_files() {
+    let dir = std::env::temp_dir().join("music-stats-lastfm-import");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("export.csv"), EXPORT_CSV).unwrap();
+    std::fs::write(dir.join(".scrobbler.log"), SCROBBLER_LOG).unwrap();
+    std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+    let scrobbles = read_scrobbles(&LastFmImportConfig { paths: vec![dir] }).unwrap();
+
+    assert_eq!(scrobbles.len(), 4);
+}
+
+#[test]
+fn reads_other_log_files_named_directly_as_csv() {
+    let dir = std::env::temp_dir().join("music-stats-lastfm-import-direct");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("backup.log");
+    std::fs::write(&path, EXPORT_CSV).unwrap();
+
+    let scrobbles = read_scrobbles(&LastFmImportConfig { paths: vec![path] }).unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.title, "Airbag");
+}
@@ -0,0 +1,153 @@
+use chrono::{TimeZone, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::listenbrainz::{ListenBrainzConfig, fetch_scrobbles};
+use music_stats::providers::source::TimeWindow;
+use serde_json::{Value, json};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn config(server: &MockServer) -> ListenBrainzConfig {
+    ListenBrainzConfig {
+        username: "someone".into(),
+        token: Some("secret".into()),
+        api_root: server.uri(),
+    }
+}
+
+fn window(from: i64, to: i64) -> TimeWindow {
+    TimeWindow {
+        from: Utc.timestamp_opt(from, 0).unwrap(),
+        to: Utc.timestamp_opt(to, 0).unwrap(),
+    }
+}
+
+fn listen(listened_at: i64, artist: &str, track: &str) -> Value {
+    json!({
+        "listened_at": listened_at,
+        "track_metadata": {
+            "artist_name": artist,
+            "track_name": track,
+            "release_name": "Album"
+        }
+    })
+}
+
+fn page(listens: Vec<Value>) -> Value {
+    json!({"payload": {"count": listens.len(), "user_id": "someone", "listens": listens}})
+}
+
+#[tokio::test]
+async fn maps_listens_inside_window() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/1/user/someone/listens"))
+        .and(query_param("max_ts", "2001"))
+        .and(header("Authorization", "Token secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![
+            listen(1900, "Artist", "Inside"),
+            listen(500, "Artist", "Too old"),
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(1000, 2000),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.artist, "Artist");
+    assert_eq!(scrobbles[0].track.title, "Inside");
+    assert_eq!(scrobbles[0].played_at.timestamp(), 1900);
+}
+
+#[tokio::test]
+async fn pages_backwards_with_max_ts() {
+    let server = MockServer::start().await;
+    let full_page = (0..1000)
+        .map(|i| listen(100_000 - i, "Artist", "Track"))
+        .collect();
+    Mock::given(path("/1/user/someone/listens"))
+        .and(query_param("max_ts", "100001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(full_page)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/1/user/someone/listens"))
+        .and(query_param("max_ts", "99002"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![
+            listen(99_001, "Artist", "Track"),
+            listen(99_000, "Artist", "Last"),
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(0, 100_000),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 1001);
+}
+
+#[tokio::test]
+async fn keeps_listens_sharing_the_oldest_second_of_a_page() {
+    let server = MockServer::start().await;
+    // The page is full at 99 001; another listen from that second did not fit.
+    let full_page = (0..1000)
+        .map(|i| listen(100_000 - i, "Artist", "Track"))
+        .collect();
+    Mock::given(path("/1/user/someone/listens"))
+        .and(query_param("max_ts", "100001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(full_page)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/1/user/someone/listens"))
+        .and(query_param("max_ts", "99002"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![
+            listen(99_001, "Artist", "Track"),
+            listen(99_001, "Other", "Same second"),
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(0, 100_000),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 1001);
+    assert!(scrobbles.iter().any(|s| s.track.title == "Same second"));
+}
+
+#[tokio::test]
+async fn reports_api_errors() {
+    let server = MockServer::start().await;
+    Mock::given(path("/1/user/someone/listens"))
+        .respond_with(ResponseTemplate::new(404).set_body_string("Cannot find user"))
+        .mount(&server)
+        .await;
+
+    let result = fetch_scrobbles(&reqwest::Client::new(), &config(&server), window(0, 10)).await;
+
+    match result {
+        Err(Error::ListenBrainz { status, body, .. }) => {
+            assert_eq!(status, 404);
+            assert!(body.contains("Cannot find user"));
+        }
+        other => panic!("unexpected result: {:?}", other.map(|s| s.len())),
+    }
+}
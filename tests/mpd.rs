@@ -0,0 +1,274 @@
+use chrono::{TimeZone, Utc};
+use music_stats::providers::mpd::{
+    MpdConfig, PlayTracker, PlayerState, Song, read_history, record,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::time::{Instant, timeout};
+
+fn song(id: &str, seconds: u64) -> Option<Song> {
+    Some(Song {
+        id: id.to_string(),
+        artist: Some("Radiohead".to_string()),
+        title: Some(format!("Track {}", id)),
+        album: Some("OK Computer".to_string()),
+        duration: Some(Duration::from_secs(seconds)),
+    })
+}
+
+fn playing(song: Option<Song>, elapsed: u64) -> PlayerState {
+    PlayerState {
+        playing: true,
+        elapsed: Some(Duration::from_secs(elapsed)),
+        song,
+    }
+}
+
+fn config() -> MpdConfig {
+    MpdConfig {
+        address: "fake".to_string(),
+        password: Some("se\"cret".to_string()),
+        history: PathBuf::from("unused.jsonl"),
+    }
+}
+
+#[test]
+fn scrobbles_after_half_the_track() {
+    let start = Instant::now();
+    let wall = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    let mut tracker = PlayTracker::default();
+
+    assert!(
+        tracker
+            .update(start, wall, playing(song("1", 200), 0))
+            .is_none()
+    );
+    assert_eq!(tracker.remaining(start), Some(Duration::from_secs(100)));
+
+    let later = start + Duration::from_secs(100);
+    let scrobble = tracker.update(later, wall, playing(song("1", 200), 100));
+    let scrobble = scrobble.expect("half the track has played");
+    assert_eq!(scrobble.track.title, "Track 1");
+    assert_eq!(scrobble.played_at, wall);
+    assert_eq!(scrobble.album.as_deref(), Some("OK Computer"));
+
+    // Counted once only.
+    assert_eq!(tracker.remaining(later), None);
+    let end = later + Duration::from_secs(50);
+    assert!(
+        tracker
+            .update(end, wall, playing(song("1", 200), 150))
+            .is_none()
+    );
+}
+
+#[test]
+fn caps_the_wait_at_four_minutes() {
+    let start = Instant::now();
+    let mut tracker = PlayTracker::default();
+    tracker.update(start, Utc::now(), playing(song("1", 1200), 0));
+
+    assert_eq!(tracker.remaining(start), Some(Duration::from_secs(240)));
+}
+
+#[test]
+fn ignores_paused_time_and_skipped_tracks() {
+    let start = Instant::now();
+    let wall = Utc::now();
+    let mut tracker = PlayTracker::default();
+
+    tracker.update(start, wall, playing(song("1", 200), 0));
+    let paused = PlayerState {
+        playing: false,
+        ..playing(song("1", 200), 60)
+    };
+    let pause_at = start + Duration::from_secs(60);
+    assert!(tracker.update(pause_at, wall, paused).is_none());
+    assert_eq!(tracker.remaining(pause_at), None);
+
+    // Resumed much later: only the 60 seconds before the pause count.
+    let resume_at = pause_at + Duration::from_secs(600);
+    assert!(
+        tracker
+            .update(resume_at, wall, playing(song("1", 200), 60))
+            .is_none()
+    );
+    assert_eq!(tracker.remaining(resume_at), Some(Duration::from_secs(40)));
+
+    // Skipping to the next track before then drops the first one.
+    let skip_at = resume_at + Duration::from_secs(10);
+    assert!(
+        tracker
+            .update(skip_at, wall, playing(song("2", 200), 0))
+            .is_none()
+    );
+}
+
+#[test]
+fn never_scrobbles_short_tracks() {
+    let start = Instant::now();
+    let mut tracker = PlayTracker::default();
+    tracker.update(start, Utc::now(), playing(song("1", 20), 0));
+
+    assert_eq!(tracker.remaining(start), None);
+    let end = start + Duration::from_secs(20);
+    assert!(
+        tracker
+            .update(end, Utc::now(), playing(song("2", 200), 0))
+            .is_none()
+    );
+}
+
+#[test]
+fn counts_a_repeated_track_again() {
+    let start = Instant::now();
+    let wall = Utc::now();
+    let mut tracker = PlayTracker::default();
+
+    tracker.update(start, wall, playing(song("1", 60), 0));
+    let first = start + Duration::from_secs(30);
+    let counted = tracker.update(first, wall, playing(song("1", 60), 30));
+    assert!(counted.is_some());
+
+    // MPD reports the same song id starting over.
+    let repeat = first + Duration::from_secs(30);
+    let restarted = tracker.update(repeat, wall, playing(song("1", 60), 0));
+    assert!(restarted.is_none());
+    let second = repeat + Duration::from_secs(30);
+    let counted = tracker.update(second, wall, playing(song("1", 60), 30));
+    assert!(counted.is_some());
+}
+
+/// Plays MPD's side of the conversation: each expected command is answered
+/// with its response, or left waiting when the response is `None`.
+async fn fake_mpd(server: DuplexStream, script: Vec<(&'static str, Option<&'static str>)>) {
+    let mut server = BufReader::new(server);
+    server.write_all(b"OK MPD 0.23.5\n").await.unwrap();
+
+    for (expected, response) in script {
+        let mut line = String::new();
+        server.read_line(&mut line).await.unwrap();
+        assert_eq!(line.trim_end(), expected);
+        if let Some(response) = response {
+            server.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+}
+
+const STATUS_PLAYING: &str = "volume: 100\nstate: play\nsongid: 7\nelapsed: 0.000\nOK\n";
+const CURRENT_SONG: &str = "file: radiohead/airbag.flac\nArtist: Radiohead\nTitle: Airbag\nAlbum: OK Computer\nTime: 284\nduration: 284.160\nId: 7\nOK\n";
+
+#[tokio::test(start_paused = true)]
+async fn records_a_play_from_a_scripted_server() {
+    let (client, server) = tokio::io::duplex(4096);
+    let script = vec![
+        ("password \"se\\\"cret\"", Some("OK\n")),
+        ("status", Some(STATUS_PLAYING)),
+        ("currentsong", Some(CURRENT_SONG)),
+        // Nothing happens, so the listener wakes itself once the track counts.
+        ("idle player", None),
+        ("noidle", Some("OK\n")),
+        ("status", Some(STATUS_PLAYING)),
+        ("currentsong", Some(CURRENT_SONG)),
+        ("idle player", None),
+    ];
+    let server = tokio::spawn(fake_mpd(server, script));
+
+    let started = Instant::now();
+    let mut scrobbles = Vec::new();
+    let config = config();
+    let recording = record(client, &config, |scrobble| {
+        scrobbles.push(scrobble);
+        Ok(())
+    });
+
+    // The fake server hangs up once the script is done.
+    let (result, served) = tokio::join!(recording, server);
+    served.unwrap();
+    result.unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.artist, "Radiohead");
+    assert_eq!(scrobbles[0].track.title, "Airbag");
+    assert_eq!(started.elapsed().as_secs(), 142);
+}
+
+#[tokio::test(start_paused = true)]
+async fn keeps_an_idle_response_that_arrives_around_noidle() {
+    let (client, server) = tokio::io::duplex(4096);
+    let script = vec![
+        ("password \"se\\\"cret\"", Some("OK\n")),
+        ("status", Some(STATUS_PLAYING)),
+        ("currentsong", Some(CURRENT_SONG)),
+        // MPD is halfway through its answer when the wait runs out.
+        ("idle player", Some("changed: player\nO")),
+        ("noidle", Some("K\n")),
+        ("status", Some(STATUS_PLAYING)),
+        ("currentsong", Some(CURRENT_SONG)),
+        ("idle player", None),
+    ];
+    let server = tokio::spawn(fake_mpd(server, script));
+
+    let mut scrobbles = Vec::new();
+    let config = config();
+    let recording = record(client, &config, |scrobble| {
+        scrobbles.push(scrobble);
+        Ok(())
+    });
+
+    let (result, served) = timeout(Duration::from_secs(600), async {
+        tokio::join!(recording, server)
+    })
+    .await
+    .expect("the listener stopped answering");
+    served.unwrap();
+    result.unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+}
+
+#[tokio::test]
+async fn reports_protocol_errors() {
+    let (client, server) = tokio::io::duplex(4096);
+    let script = vec![(
+        "password \"se\\\"cret\"",
+        Some("ACK [3@0] {password} incorrect password\n"),
+    )];
+    let server = tokio::spawn(fake_mpd(server, script));
+
+    let config = config();
+    let error = record(client, &config, |_| Ok(())).await.unwrap_err();
+    server.await.unwrap();
+
+    assert!(error.to_string().contains("incorrect password"));
+}
+
+#[test]
+fn reads_recorded_history() {
+    let path = std::env::temp_dir().join("music-stats-mpd-history.jsonl");
+    std::fs::write(
+        &path,
+        r#"{"played_at":"2024-01-15T12:34:00Z","artist":"Radiohead","title":"Airbag","album":"OK Computer"}
+not json
+{"played_at":"2024-01-15T12:40:00Z","artist":"Portishead","title":"Roads"}
+"#,
+    )
+    .unwrap();
+
+    let recorded = MpdConfig {
+        history: path,
+        ..config()
+    };
+    let scrobbles = read_history(&recorded).unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].album.as_deref(), Some("OK Computer"));
+    assert_eq!(scrobbles[1].played_at.timestamp(), 1705322400);
+
+    let missing = MpdConfig {
+        history: PathBuf::from("/nonexistent/history.jsonl"),
+        ..config()
+    };
+    assert!(read_history(&missing).unwrap().is_empty());
+}
@@ -0,0 +1,124 @@
+use chrono::{Duration, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::registry::Registry;
+use music_stats::providers::source::{BoxFuture, Capabilities, ScrobbleSource, TimeWindow};
+use music_stats::providers::types::{Scrobble, Track};
+
+struct FakeSource {
+    name: &'static str,
+    plays: usize,
+}
+
+impl ScrobbleSource for FakeSource {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async move {
+            Ok((0..self.plays)
+                .map(|_| Scrobble::new(self.name.into(), "Track".into(), window.to))
+                .collect())
+        })
+    }
+}
+
+#[tokio::test]
+async fn fetches_from_every_registered_source() {
+    let mut registry = Registry::default();
+    registry.register(Box::new(FakeSource {
+        name: "one",
+        plays: 2,
+    }));
+    registry.register(Box::new(FakeSource {
+        name: "two",
+        plays: 3,
+    }));
+
+    let scrobbles = registry
+        .fetch_all(&reqwest::Client::new(), TimeWindow::last_days(7))
+        .await
+        .unwrap();
+
+    assert_eq!(registry.names(), vec!["one", "two"]);
+    assert_eq!(scrobbles.len(), 5);
+}
+
+#[test]
+fn empty_registry_has_no_sources() {
+    let registry = Registry::default();
+    assert!(registry.is_empty());
+}
+
+#[test]
+fn window_covers_requested_days() {
+    let window = TimeWindow::last_days(7);
+
+    assert!(window.contains(Utc::now() - Duration::days(6)));
+    assert!(!window.contains(Utc::now() - Duration::days(8)));
+}
+
+struct PlayingSource {
+    playing: Result<Option<&'static str>, ()>,
+}
+
+impl ScrobbleSource for PlayingSource {
+    fn name(&self) -> &str {
+        "playing"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        _window: TimeWindow,
+    ) -> BoxFuture<'a, Result<Vec<Scrobble>, Error>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn now_playing<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+    ) -> BoxFuture<'a, Result<Option<Track>, Error>> {
+        Box::pin(async move {
+            match self.playing {
+                Ok(title) => Ok(title.map(|title| Track {
+                    artist: "Artist".into(),
+                    title: title.into(),
+                })),
+                Err(()) => Err(Error::NoProviders),
+            }
+        })
+    }
+}
+
+#[tokio::test]
+async fn now_playing_comes_from_the_first_source_that_knows() {
+    let mut registry = Registry::default();
+    registry.register(Box::new(FakeSource {
+        name: "silent",
+        plays: 1,
+    }));
+    registry.register(Box::new(PlayingSource { playing: Err(()) }));
+    registry.register(Box::new(PlayingSource {
+        playing: Ok(Some("First")),
+    }));
+    registry.register(Box::new(PlayingSource {
+        playing: Ok(Some("Second")),
+    }));
+
+    let track = registry.now_playing(&reqwest::Client::new()).await;
+
+    assert_eq!(track.map(|t| t.title), Some("First".to_string()));
+}
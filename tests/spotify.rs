@@ -0,0 +1,245 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::source::TimeWindow;
+use music_stats::providers::spotify::{SpotifyConfig, fetch_scrobbles};
+use serde_json::{Value, json};
+use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn config(server: &MockServer) -> SpotifyConfig {
+    SpotifyConfig {
+        client_id: "client".into(),
+        client_secret: Some("shh".into()),
+        refresh_token: "refresh".into(),
+        api_root: server.uri(),
+        token_url: format!("{}/api/token", server.uri()),
+    }
+}
+
+fn window(from: i64, to: i64) -> TimeWindow {
+    TimeWindow {
+        from: Utc.timestamp_opt(from, 0).unwrap(),
+        to: Utc.timestamp_opt(to, 0).unwrap(),
+    }
+}
+
+fn item(played_at: DateTime<Utc>, title: &str) -> Value {
+    json!({
+        "played_at": played_at.to_rfc3339(),
+        "context": null,
+        "track": {
+            "name": title,
+            "duration_ms": 200_000,
+            "artists": [{"name": "Daft Punk"}, {"name": "Pharrell Williams"}],
+            "album": {"name": "Random Access Memories"}
+        }
+    })
+}
+
+async fn mount_token(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/api/token"))
+        // base64("client:shh")
+        .and(header("authorization", "Basic Y2xpZW50OnNoaA=="))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .and(body_string_contains("refresh_token=refresh"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access",
+            "token_type": "Bearer",
+            "expires_in": 3600
+        })))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn pages_back_with_the_before_cursor() {
+    let server = MockServer::start().await;
+    mount_token(&server).await;
+
+    let newest = Utc.timestamp_opt(100_000, 0).unwrap();
+    let first: Vec<Value> = (0..50)
+        .map(|i| item(newest - Duration::minutes(i), "Get Lucky"))
+        .collect();
+    let oldest_first = newest - Duration::minutes(49);
+    let cursor = oldest_first.timestamp_millis().to_string();
+
+    Mock::given(method("GET"))
+        .and(path("/v1/me/player/recently-played"))
+        .and(header("authorization", "Bearer access"))
+        .and(query_param("before", cursor.as_str()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [item(oldest_first - Duration::minutes(5), "Lose Yourself to Dance")],
+            "cursors": null
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/me/player/recently-played"))
+        .and(header("authorization", "Bearer access"))
+        .and(query_param("limit", "50"))
+        .and(query_param("before", "100000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": first,
+            "cursors": {"before": cursor, "after": "100000000"}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(90_000, 100_000),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 51);
+    assert_eq!(scrobbles[0].track.artist, "Daft Punk, Pharrell Williams");
+    assert_eq!(
+        scrobbles[0].album.as_deref(),
+        Some("Random Access Memories")
+    );
+    // Spotify's played_at is kept as it is, with the length next to it.
+    assert_eq!(scrobbles[0].played_at, newest);
+    assert_eq!(
+        scrobbles[0].metadata.duration,
+        Some(std::time::Duration::from_secs(200))
+    );
+    assert_eq!(scrobbles[50].track.title, "Lose Yourself to Dance");
+}
+
+#[tokio::test]
+async fn stops_when_a_page_is_not_full() {
+    let server = MockServer::start().await;
+    mount_token(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/v1/me/player/recently-played"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [
+                item(Utc.timestamp_opt(5_000, 0).unwrap(), "Too new"),
+                item(Utc.timestamp_opt(1_500, 0).unwrap(), "Instant Crush"),
+                item(Utc.timestamp_opt(900, 0).unwrap(), "Too old")
+            ],
+            "cursors": {"before": "900000", "after": "5000000"}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(1_000, 2_000),
+    )
+    .await
+    .unwrap();
+
+    let titles: Vec<&str> = scrobbles.iter().map(|s| s.track.title.as_str()).collect();
+    assert_eq!(titles, vec!["Instant Crush"]);
+}
+
+#[tokio::test]
+async fn sends_the_client_id_in_the_form_without_a_secret() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/token"))
+        .and(body_string_contains("client_id=client"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access",
+            "refresh_token": "refresh"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/me/player/recently-played"))
+        .and(header("authorization", "Bearer access"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"items": []})))
+        .mount(&server)
+        .await;
+
+    let config = SpotifyConfig {
+        client_secret: None,
+        ..config(&server)
+    };
+    let scrobbles = fetch_scrobbles(&reqwest::Client::new(), &config, window(1_000, 2_000))
+        .await
+        .unwrap();
+
+    assert!(scrobbles.is_empty());
+}
+
+#[tokio::test]
+async fn reports_token_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/token"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": "invalid_grant",
+            "error_description": "Invalid refresh token"
+        })))
+        .mount(&server)
+        .await;
+
+    let error = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(1_000, 2_000),
+    )
+    .await
+    .unwrap_err();
+
+    match error {
+        Error::Spotify { status, url, body } => {
+            assert_eq!(status, 400);
+            assert!(url.ends_with("/api/token"));
+            assert!(body.contains("invalid_grant"));
+        }
+        other => panic!("expected a Spotify error, got {}", other),
+    }
+}
+
+#[tokio::test]
+async fn stops_at_the_page_cap() {
+    let server = MockServer::start().await;
+    mount_token(&server).await;
+
+    // Every page is full and ends a minute before the cursor it was asked for.
+    Mock::given(method("GET"))
+        .and(path("/v1/me/player/recently-played"))
+        .respond_with(|request: &wiremock::Request| {
+            let before: i64 = request
+                .url
+                .query_pairs()
+                .find(|(name, _)| name == "before")
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap();
+            let newest = Utc.timestamp_millis_opt(before).unwrap() - Duration::seconds(1);
+            let items: Vec<Value> = (0..50)
+                .map(|i| item(newest - Duration::seconds(i), "Get Lucky"))
+                .collect();
+            let oldest = newest - Duration::seconds(49);
+            ResponseTemplate::new(200).set_body_json(json!({
+                "items": items,
+                "cursors": {"before": oldest.timestamp_millis().to_string()}
+            }))
+        })
+        .expect(20)
+        .mount(&server)
+        .await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        window(0, 100_000),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 20 * 50);
+}
@@ -0,0 +1,122 @@
+use music_stats::providers::spotify_export::{SpotifyExportConfig, parse_history, read_history};
+use std::io::Write;
+use std::path::PathBuf;
+
+const HISTORY: &str = r#"[
+    {
+        "ts": "2024-01-15T12:03:00Z",
+        "ms_played": 180000,
+        "master_metadata_track_name": "Song",
+        "master_metadata_album_artist_name": "Artist",
+        "master_metadata_album_album_name": "Album",
+        "spotify_track_uri": "spotify:track:abc",
+        "episode_name": null
+    },
+    {
+        "ts": "2024-01-15T12:05:00Z",
+        "ms_played": 4000,
+        "master_metadata_track_name": "Skipped",
+        "master_metadata_album_artist_name": "Artist",
+        "episode_name": null
+    },
+    {
+        "ts": "2024-01-15T13:00:00Z",
+        "ms_played": 1800000,
+        "master_metadata_track_name": null,
+        "master_metadata_album_artist_name": null,
+        "episode_name": "A podcast"
+    }
+]"#;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("music-stats-spotify-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn converts_entries_to_scrobbles() {
+    let scrobbles = parse_history(HISTORY, 0).unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.artist, "Artist");
+    assert_eq!(scrobbles[0].track.title, "Song");
+    assert_eq!(
+        scrobbles[0].played_at.to_rfc3339(),
+        "2024-01-15T12:00:00+00:00"
+    );
+}
+
+#[test]
+fn drops_short_plays_below_threshold() {
+    let scrobbles = parse_history(HISTORY, 30_000).unwrap();
+
+    assert_eq!(scrobbles.len(), 1);
+    assert_eq!(scrobbles[0].track.title, "Song");
+}
+
+#[test]
+fn fails_on_malformed_json() {
+    assert!(parse_history("{not json", 0).is_err());
+}
+
+#[test]
+fn reads_history_files_from_zip() {
+    let dir = temp_dir("zip");
+    let archive_path = dir.join("my_spotify_data.zip");
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    writer
+        .start_file(
+            "Spotify Extended Streaming History/Streaming_History_Audio_2024.json",
+            options,
+        )
+        .unwrap();
+    writer.write_all(HISTORY.as_bytes()).unwrap();
+    writer
+        .start_file(
+            "Spotify Extended Streaming History/Streaming_History_Video_2024.json",
+            options,
+        )
+        .unwrap();
+    writer.write_all(b"not read").unwrap();
+    writer.finish().unwrap();
+
+    let scrobbles = read_history(&SpotifyExportConfig {
+        paths: vec![archive_path],
+        min_played_ms: 0,
+    })
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 2);
+}
+
+#[test]
+fn reads_files_and_folders() {
+    let dir = temp_dir("folder");
+    std::fs::write(dir.join("Streaming_History_Audio_2023.json"), HISTORY).unwrap();
+    std::fs::write(dir.join("ReadMeFirst.pdf"), "ignored").unwrap();
+    let single = temp_dir("single").join("export.json");
+    std::fs::write(&single, HISTORY).unwrap();
+
+    let scrobbles = read_history(&SpotifyExportConfig {
+        paths: vec![dir, single],
+        min_played_ms: 0,
+    })
+    .unwrap();
+
+    assert_eq!(scrobbles.len(), 4);
+}
+
+#[test]
+fn reports_missing_file() {
+    let result = read_history(&SpotifyExportConfig {
+        paths: vec![PathBuf::from(
+            "/nonexistent/Streaming_History_Audio_2024.json",
+        )],
+        min_played_ms: 0,
+    });
+
+    assert!(format!("{}", result.unwrap_err()).contains("/nonexistent"));
+}
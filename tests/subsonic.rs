@@ -0,0 +1,202 @@
+use chrono::{Duration, SecondsFormat, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::source::TimeWindow;
+use music_stats::providers::subsonic::{SubsonicConfig, fetch_scrobbles, token};
+use serde_json::{Value, json};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Match, Mock, MockServer, Request, ResponseTemplate};
+
+/// Accepts requests whose token matches the password and the salt sent along.
+struct SaltedToken(&'static str);
+
+impl Match for SaltedToken {
+    fn matches(&self, request: &Request) -> bool {
+        let query: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+        };
+        match (param("t"), param("s")) {
+            (Some(t), Some(s)) => !s.is_empty() && t == token(self.0, &s),
+            _ => false,
+        }
+    }
+}
+
+fn config(server: &MockServer) -> SubsonicConfig {
+    SubsonicConfig {
+        name: "Navidrome".into(),
+        url: server.uri(),
+        username: "someone".into(),
+        password: "sesame".into(),
+    }
+}
+
+fn ago(hours: i64) -> String {
+    (Utc::now() - Duration::hours(hours)).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn ok(body: Value) -> ResponseTemplate {
+    let mut response = json!({"status": "ok", "version": "1.16.1"});
+    response
+        .as_object_mut()
+        .unwrap()
+        .extend(body.as_object().unwrap().clone());
+    ResponseTemplate::new(200).set_body_json(json!({ "subsonic-response": response }))
+}
+
+async fn mount(server: &MockServer, endpoint: &str, body: Value) {
+    Mock::given(method("GET"))
+        .and(path(format!("/rest/{}", endpoint)))
+        .and(query_param("u", "someone"))
+        .and(query_param("f", "json"))
+        .and(SaltedToken("sesame"))
+        .respond_with(ok(body))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn combines_recent_albums_and_now_playing() {
+    let server = MockServer::start().await;
+    mount(
+        &server,
+        "getAlbumList2",
+        json!({"albumList2": {"album": [
+            {"id": "a1", "name": "OK Computer", "played": ago(2)},
+            {"id": "a2", "name": "Old", "played": ago(100)}
+        ]}}),
+    )
+    .await;
+    mount(
+        &server,
+        "getAlbum",
+        json!({"album": {"id": "a1", "song": [
+            {"id": "s1", "title": "Airbag", "artist": "Radiohead", "album": "OK Computer", "played": ago(2)},
+            {"id": "s2", "title": "Lucky", "artist": "Radiohead", "album": "OK Computer", "played": ago(200)},
+            {"id": "s3", "title": "Unplayed", "artist": "Radiohead", "album": "OK Computer"}
+        ]}}),
+    )
+    .await;
+    mount(
+        &server,
+        "getNowPlaying",
+        json!({"nowPlaying": {"entry": [
+            {"id": "s4", "title": "Roads", "artist": "Portishead", "username": "someone", "minutesAgo": 1},
+            {"id": "s5", "title": "Theirs", "artist": "Other", "username": "someone_else", "minutesAgo": 0}
+        ]}}),
+    )
+    .await;
+
+    let window = TimeWindow::last_days(1);
+    let scrobbles = fetch_scrobbles(&reqwest::Client::new(), &config(&server), window)
+        .await
+        .unwrap();
+
+    let titles: Vec<&str> = scrobbles.iter().map(|s| s.track.title.as_str()).collect();
+    assert_eq!(titles, vec!["Airbag", "Roads"]);
+    assert_eq!(scrobbles[0].album.as_deref(), Some("OK Computer"));
+}
+
+#[tokio::test]
+async fn pages_through_recent_albums() {
+    let server = MockServer::start().await;
+    let full_page: Vec<Value> = (0..500)
+        .map(|i| json!({"id": format!("a{}", i), "played": ago(2)}))
+        .collect();
+    Mock::given(method("GET"))
+        .and(path("/rest/getAlbumList2"))
+        .and(query_param("offset", "0"))
+        .respond_with(ok(json!({"albumList2": {"album": full_page}})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/getAlbumList2"))
+        .and(query_param("offset", "500"))
+        .respond_with(ok(json!({"albumList2": {"album": [
+            {"id": "last", "played": ago(3)},
+            {"id": "old", "played": ago(100)}
+        ]}})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/getAlbum"))
+        .and(query_param("id", "last"))
+        .respond_with(ok(json!({"album": {"id": "last", "song": [
+            {"id": "s1", "title": "Airbag", "artist": "Radiohead", "played": ago(3)}
+        ]}})))
+        .mount(&server)
+        .await;
+    mount(
+        &server,
+        "getAlbum",
+        json!({"album": {"id": "a", "song": []}}),
+    )
+    .await;
+    mount(&server, "getNowPlaying", json!({"nowPlaying": {}})).await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        TimeWindow::last_days(1),
+    )
+    .await
+    .unwrap();
+
+    let titles: Vec<&str> = scrobbles.iter().map(|s| s.track.title.as_str()).collect();
+    assert_eq!(titles, vec!["Airbag"]);
+}
+
+#[tokio::test]
+async fn handles_servers_without_history() {
+    let server = MockServer::start().await;
+    mount(&server, "getAlbumList2", json!({"albumList2": {}})).await;
+    mount(&server, "getNowPlaying", json!({"nowPlaying": {}})).await;
+
+    let scrobbles = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        TimeWindow::last_days(1),
+    )
+    .await
+    .unwrap();
+
+    assert!(scrobbles.is_empty());
+}
+
+#[tokio::test]
+async fn reports_api_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/getAlbumList2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subsonic-response": {
+                "status": "failed",
+                "version": "1.16.1",
+                "error": {"code": 40, "message": "Wrong username or password"}
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let error = fetch_scrobbles(
+        &reqwest::Client::new(),
+        &config(&server),
+        TimeWindow::last_days(1),
+    )
+    .await
+    .unwrap_err();
+
+    match error {
+        Error::Subsonic { url, detail } => {
+            assert!(url.ends_with("/rest/getAlbumList2"));
+            assert!(!url.contains("sesame"));
+            assert!(detail.contains("Wrong username or password"));
+        }
+        other => panic!("expected a Subsonic error, got {}", other),
+    }
+}
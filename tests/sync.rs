@@ -0,0 +1,205 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use music_stats::errors::Error;
+use music_stats::providers::history;
+use music_stats::providers::lastfm::LastFmConfig;
+use music_stats::providers::lastfm_signed::{MAX_BATCH, scrobble, sign};
+use music_stats::providers::source::TimeWindow;
+use music_stats::providers::types::Scrobble;
+use music_stats::sync::{SyncConfig, plan, submit, unsynced};
+use serde_json::json;
+use std::path::PathBuf;
+use wiremock::matchers::{body_string_contains, method, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 3, 2, hour, minute, 0).unwrap()
+}
+
+fn play(title: &str, played_at: DateTime<Utc>) -> Scrobble {
+    Scrobble::new("Björk".into(), title.into(), played_at)
+}
+
+fn ledger_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("music-stats-sync-{}.jsonl", name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn config(server: &MockServer, ledger: PathBuf) -> SyncConfig {
+    let lastfm = LastFmConfig {
+        name: "Last.fm".into(),
+        api_key: "key".into(),
+        username: "someone".into(),
+        api_root: server.uri(),
+        max_pages: None,
+        api_secret: Some("secret".into()),
+        session_key: Some("session".into()),
+    };
+    SyncConfig {
+        youtube_cookie: "cookie".into(),
+        session: lastfm.session().unwrap(),
+        lastfm,
+        ledger,
+        tolerance: Duration::hours(12),
+    }
+}
+
+#[test]
+fn signs_sorted_parameters_with_the_secret() {
+    let params = vec![
+        ("token".to_string(), "tok".to_string()),
+        ("method".to_string(), "auth.getSession".to_string()),
+        ("format".to_string(), "json".to_string()),
+        ("api_key".to_string(), "key".to_string()),
+    ];
+
+    assert_eq!(sign(&params, "secret"), "04e870be4bb79756721b7bc1937fe83d");
+}
+
+#[test]
+fn skips_plays_already_scrobbled_within_the_tolerance() {
+    let plays = vec![
+        play("Jóga", at(12, 0)),
+        play("Hunter", at(12, 0)),
+        play("Bachelorette", at(12, 0)),
+    ];
+    let scrobbled = vec![
+        Scrobble::new("björk".into(), "JÓGA".into(), at(9, 30)),
+        // Too far from the play to be the same one.
+        Scrobble::new(
+            "Björk".into(),
+            "Hunter".into(),
+            at(12, 0) - Duration::days(1),
+        ),
+    ];
+
+    let plan = unsynced(plays, &[], &scrobbled, Duration::hours(12));
+
+    let titles: Vec<&str> = plan
+        .pending
+        .iter()
+        .map(|s| s.track.title.as_str())
+        .collect();
+    assert_eq!(titles, vec!["Hunter", "Bachelorette"]);
+    assert_eq!(plan.on_lastfm, 1);
+    assert_eq!(plan.in_ledger, 0);
+}
+
+#[test]
+fn counts_repeated_plays_once_each() {
+    // Played twice today. The first run sent one play, which is now both in
+    // the ledger and on Last.fm; only the second is left.
+    let plays = vec![play("Jóga", at(12, 0)), play("Jóga", at(12, 0))];
+    let ledger = vec![play("Jóga", at(12, 0))];
+    let scrobbled = vec![play("Jóga", at(12, 0))];
+
+    let plan = unsynced(plays, &ledger, &scrobbled, Duration::hours(12));
+
+    assert_eq!(plan.pending.len(), 1);
+    assert_eq!(plan.in_ledger, 1);
+    assert_eq!(plan.on_lastfm, 0);
+}
+
+#[tokio::test]
+async fn submits_in_batches_and_never_twice() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("method=track.scrobble"))
+        .and(body_string_contains("sk=session"))
+        .and(body_string_contains("api_sig="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "scrobbles": {"scrobble": [], "@attr": {"accepted": 50, "ignored": 0}}
+        })))
+        .expect(3)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(query_param("method", "user.getrecenttracks"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "recenttracks": {"track": [], "@attr": {"totalPages": "0"}}
+        })))
+        .mount(&server)
+        .await;
+
+    let ledger = ledger_path("batches");
+    let config = config(&server, ledger.clone());
+    let plays: Vec<Scrobble> = (0..120)
+        .map(|i| play(&format!("Track {}", i % 40), at(12, 0)))
+        .collect();
+    let window = TimeWindow {
+        from: at(0, 0) - Duration::days(7),
+        to: at(23, 0),
+    };
+
+    let first = plan(&reqwest::Client::new(), &config, plays.clone(), window)
+        .await
+        .unwrap();
+    assert_eq!(first.pending.len(), 120);
+    let submitted = submit(&reqwest::Client::new(), &config, &first.pending, at(18, 0))
+        .await
+        .unwrap();
+    assert_eq!(submitted.accepted, 150);
+    assert_eq!(history::read(&ledger).unwrap().len(), 120);
+
+    let requests = server.received_requests().await.unwrap();
+    let batch_sizes: Vec<usize> = requests
+        .iter()
+        .filter(|request| request.method.as_str() == "POST")
+        .map(|request| {
+            let body = String::from_utf8_lossy(&request.body);
+            body.matches("artist%5B").count()
+        })
+        .collect();
+    assert_eq!(batch_sizes, vec![50, 50, 20]);
+
+    let second = plan(&reqwest::Client::new(), &config, plays, window)
+        .await
+        .unwrap();
+    assert!(second.pending.is_empty());
+    assert_eq!(second.in_ledger, 120);
+}
+
+#[tokio::test]
+async fn spreads_plays_that_share_a_time_and_keeps_them_in_the_past() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "scrobbles": {"scrobble": {}, "@attr": {"accepted": "2", "ignored": "0"}}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = config(&server, ledger_path("spread"));
+    let pending = vec![play("Jóga", at(12, 0)), play("Jóga", at(12, 0))];
+    let now = at(10, 0);
+
+    submit(&reqwest::Client::new(), &config, &pending, now)
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let body = String::from_utf8_lossy(&requests[0].body).to_string();
+    assert!(body.contains(&format!("timestamp%5B0%5D={}", now.timestamp())));
+    assert!(body.contains(&format!("timestamp%5B1%5D={}", now.timestamp() - 1)));
+}
+
+#[tokio::test]
+async fn rejects_batches_over_the_limit() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let config = config(&server, ledger_path("oversized"));
+    let batch: Vec<Scrobble> = (0..=MAX_BATCH as i64)
+        .map(|i| play("Hyperballad", at(10, 0) + Duration::minutes(i)))
+        .collect();
+    let error = scrobble(&reqwest::Client::new(), &config.session, &batch)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, Error::Sync { .. }), "got {}", error);
+}
@@ -0,0 +1,211 @@
+use chrono::{TimeZone, Utc};
+use hyper::body::Bytes;
+use music_stats::providers::history;
+use music_stats::providers::types::Scrobble;
+use music_stats::providers::webhook::{WebhookConfig, parse_event, serve};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+const BOUNDARY: &str = "------------------------d74496d66958873e";
+
+fn plex(event: &str, account: &str) -> Value {
+    json!({
+        "event": event,
+        "user": true,
+        "owner": true,
+        "Account": {"id": 1, "title": account},
+        "Server": {"title": "nas"},
+        "Metadata": {
+            "type": "track",
+            "title": "Airbag",
+            "grandparentTitle": "Radiohead",
+            "parentTitle": "OK Computer",
+            "duration": 290000
+        }
+    })
+}
+
+fn jellyfin(position_seconds: u64, completed: bool) -> Value {
+    json!({
+        "NotificationType": "PlaybackStop",
+        "ItemType": "Audio",
+        "Name": "Roads",
+        "Artist": "Portishead",
+        "Album": "Dummy",
+        "NotificationUsername": "someone",
+        "PlayedToCompletion": completed,
+        "PlaybackPositionTicks": position_seconds * 10_000_000,
+        "RunTimeTicks": 305u64 * 10_000_000
+    })
+}
+
+/// Builds the multipart body Plex sends, cover art included.
+fn multipart(payload: &Value) -> Vec<u8> {
+    let mut body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"payload\"\r\nContent-Type: application/json\r\n\r\n{p}\r\n--{b}\r\nContent-Disposition: form-data; name=\"thumb\"; filename=\"image.jpg\"\r\nContent-Type: image/jpeg\r\n\r\n",
+        b = BOUNDARY,
+        p = payload
+    )
+    .into_bytes();
+    body.extend_from_slice(&[0xff, 0xd8, 0xff, 0xe0]);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+fn content_type() -> String {
+    format!("multipart/form-data; boundary={}", BOUNDARY)
+}
+
+fn now() -> chrono::DateTime<Utc> {
+    Utc.timestamp_opt(1_705_322_400, 0).unwrap()
+}
+
+#[tokio::test]
+async fn reads_plex_multipart_scrobbles() {
+    let body = Bytes::from(multipart(&plex("media.scrobble", "someone")));
+    let event = parse_event(Some(&content_type()), body).await.unwrap();
+
+    let scrobble = event.into_scrobble(Some("someone"), now()).unwrap();
+    assert_eq!(scrobble.track.artist, "Radiohead");
+    assert_eq!(scrobble.track.title, "Airbag");
+    assert_eq!(scrobble.album.as_deref(), Some("OK Computer"));
+    // Plex reports the play at 90% of the track.
+    assert_eq!(scrobble.played_at.timestamp(), 1_705_322_400 - 261);
+}
+
+#[tokio::test]
+async fn ignores_other_plex_events_and_accounts() {
+    let body = Bytes::from(multipart(&plex("media.play", "someone")));
+    let event = parse_event(Some(&content_type()), body).await.unwrap();
+    assert!(event.into_scrobble(None, now()).is_none());
+
+    let body = Bytes::from(multipart(&plex("media.scrobble", "guest")));
+    let event = parse_event(Some(&content_type()), body).await.unwrap();
+    assert!(event.into_scrobble(Some("someone"), now()).is_none());
+}
+
+#[tokio::test]
+async fn applies_scrobble_rule_to_jellyfin_stops() {
+    let played = Bytes::from(jellyfin(160, false).to_string());
+    let event = parse_event(Some("application/json"), played).await.unwrap();
+    let scrobble = event.into_scrobble(Some("someone"), now()).unwrap();
+    assert_eq!(scrobble.track.title, "Roads");
+    assert_eq!(scrobble.played_at.timestamp(), 1_705_322_400 - 160);
+
+    let skipped = Bytes::from(jellyfin(40, false).to_string());
+    let event = parse_event(None, skipped).await.unwrap();
+    assert!(event.into_scrobble(None, now()).is_none());
+
+    let completed = Bytes::from(jellyfin(0, true).to_string());
+    let event = parse_event(None, completed).await.unwrap();
+    let scrobble = event.into_scrobble(None, now()).unwrap();
+    assert_eq!(scrobble.played_at.timestamp(), 1_705_322_400 - 305);
+}
+
+#[tokio::test]
+async fn rejects_unknown_payloads() {
+    let error = parse_event(None, Bytes::from_static(b"{\"hello\": 1}"))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("not a Plex or Jellyfin webhook"));
+}
+
+#[tokio::test]
+async fn records_webhooks_into_history() {
+    let history_path = std::env::temp_dir().join("music-stats-webhook-history.jsonl");
+    let _ = std::fs::remove_file(&history_path);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let config = WebhookConfig {
+        address: address.to_string(),
+        token: Some("s3cret".to_string()),
+        username: None,
+        history: PathBuf::from(&history_path),
+    };
+    tokio::spawn(serve(listener, config));
+
+    let client = reqwest::Client::new();
+    let url = format!("http://{}/plex", address);
+
+    let denied = client
+        .post(&url)
+        .header("Content-Type", content_type())
+        .body(multipart(&plex("media.scrobble", "someone")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(denied.status(), 401);
+
+    let accepted = client
+        .post(format!("{}?token=s3cret", url))
+        .header("Content-Type", content_type())
+        .body(multipart(&plex("media.scrobble", "someone")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(accepted.status(), 200);
+
+    let ignored = client
+        .post(format!("{}?token=s3cret", url))
+        .body(jellyfin(10, false).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(ignored.status(), 200);
+
+    let recorded = history::read(&history_path).unwrap();
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0].track.title, "Airbag");
+}
+
+#[tokio::test]
+async fn decodes_the_token_before_comparing() {
+    let history_path = std::env::temp_dir().join("music-stats-webhook-token.jsonl");
+    let _ = std::fs::remove_file(&history_path);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let config = WebhookConfig {
+        address: address.to_string(),
+        token: Some("a b&c=d".to_string()),
+        username: None,
+        history: PathBuf::from(&history_path),
+    };
+    tokio::spawn(serve(listener, config));
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/?token=a+b%26c%3Dd", address))
+        .body(jellyfin(10, false).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn concurrent_appends_keep_whole_lines() {
+    let history_path = std::env::temp_dir().join("music-stats-history-concurrent.jsonl");
+    let _ = std::fs::remove_file(&history_path);
+
+    let writers: Vec<_> = (0..16)
+        .map(|writer| {
+            let path = history_path.clone();
+            std::thread::spawn(move || {
+                for play in 0..25 {
+                    let scrobble = Scrobble::new(
+                        format!("Artist {}", writer),
+                        format!("Track {} {}", play, "x".repeat(2000)),
+                        Utc.timestamp_opt(1_700_000_000 + play, 0).unwrap(),
+                    );
+                    history::append(&path, &scrobble).unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    assert_eq!(history::read(&history_path).unwrap().len(), 16 * 25);
+}
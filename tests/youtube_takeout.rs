@@ -0,0 +1,152 @@
+use music_stats::providers::youtube_takeout::{
+    YouTubeTakeoutConfig, parse_html, parse_json, read_history,
+};
+
+const HISTORY_JSON: &str = r#"[
+    {
+        "header": "YouTube Music",
+        "title": "Watched Song Title",
+        "titleUrl": "https://music.youtube.com/watch?v=abc",
+        "subtitles": [{"name": "Artist Name - Topic", "url": "https://www.youtube.com/channel/xyz"}],
+        "time": "2024-01-15T12:34:56.789Z",
+        "products": ["YouTube"]
+    },
+    {
+        "header": "YouTube",
+        "title": "Watched Some Vlog",
+        "titleUrl": "https://www.youtube.com/watch?v=def",
+        "subtitles": [{"name": "Vlogger"}],
+        "time": "2024-01-15T13:00:00Z"
+    },
+    {
+        "header": "YouTube Music",
+        "title": "Watched a video that has been removed",
+        "time": "2024-01-15T14:00:00Z"
+    },
+    {
+        "header": "YouTube Music",
+        "title": "Has visto Canción",
+        "titleUrl": "https://music.youtube.com/watch?v=ghi",
+        "time": "2024-01-16T08:00:00Z"
+    }
+]"#;
+
+const HISTORY_HTML: &str = r#"<html><body><div class="mdl-grid">
+<div class="outer-cell mdl-cell mdl-cell--12-col mdl-shadow--2dp"><div class="mdl-grid"><div class="header-cell mdl-cell mdl-cell--12-col"><p class="mdl-typography--title">YouTube Music<br></p></div><div class="content-cell mdl-cell mdl-cell--6-col mdl-typography--body-1">Watched&nbsp;<a href="https://music.youtube.com/watch?v=abc">Rock &amp; Roll</a><br><a href="https://www.youtube.com/channel/xyz">Artist Name - Topic</a><br>Jan 15, 2024, 12:34:56 PM UTC<br></div><div class="content-cell mdl-cell mdl-cell--6-col mdl-typography--body-1 mdl-typography--text-right"></div></div></div>
+<div class="outer-cell mdl-cell mdl-cell--12-col mdl-shadow--2dp"><div class="mdl-grid"><div class="header-cell mdl-cell mdl-cell--12-col"><p class="mdl-typography--title">YouTube<br></p></div><div class="content-cell mdl-cell mdl-cell--6-col mdl-typography--body-1">Watched&nbsp;<a href="https://www.youtube.com/watch?v=def">Some Vlog</a><br><a href="https://www.youtube.com/channel/v">Vlogger</a><br>Jan 15, 2024, 1:00:00 PM UTC<br></div></div></div>
+<div class="outer-cell mdl-cell mdl-cell--12-col mdl-shadow--2dp"><div class="mdl-grid"><div class="header-cell mdl-cell mdl-cell--12-col"><p class="mdl-typography--title">YouTube Music<br></p></div><div class="content-cell mdl-cell mdl-cell--6-col mdl-typography--body-1">Watched&nbsp;<a href="https://music.youtube.com/watch?v=ghi">Evening Song</a><br><a href="https://www.youtube.com/channel/z">Band</a><br>Jan 15, 2024, 8:00:00 PM PST<br></div></div></div>
+</div></body></html>"#;
+
+#[test]
+fn keeps_only_youtube_music_entries_from_json() {
+    let history = parse_json(HISTORY_JSON).unwrap();
+    let scrobbles = history.scrobbles;
+
+    assert_eq!(history.undated, 0);
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.title, "Song Title");
+    assert_eq!(scrobbles[0].track.artist, "Artist Name");
+    assert_eq!(
+        scrobbles[0].played_at.to_rfc3339(),
+        "2024-01-15T12:34:56.789+00:00"
+    );
+    assert_eq!(scrobbles[1].track.title, "Canción");
+    assert_eq!(scrobbles[1].track.artist, "Unknown Artist");
+}
+
+#[test]
+fn parses_html_variant() {
+    let history = parse_html(HISTORY_HTML);
+    let scrobbles = history.scrobbles;
+
+    assert_eq!(history.undated, 0);
+    assert_eq!(scrobbles.len(), 2);
+    assert_eq!(scrobbles[0].track.title, "Rock & Roll");
+    assert_eq!(scrobbles[0].track.artist, "Artist Name");
+    assert_eq!(
+        scrobbles[0].played_at.to_rfc3339(),
+        "2024-01-15T12:34:56+00:00"
+    );
+    assert_eq!(scrobbles[1].track.artist, "Band");
+    assert_eq!(
+        scrobbles[1].played_at.to_rfc3339(),
+        "2024-01-16T04:00:00+00:00"
+    );
+}
+
+#[test]
+fn reads_numeric_offsets_and_counts_unknown_zones() {
+    let cell = |date: &str| {
+        format!(
+            r#"<div class="outer-cell mdl-cell"><div class="mdl-grid"><div class="header-cell"><p class="mdl-typography--title">YouTube Music<br></p></div><div class="content-cell mdl-cell mdl-cell--6-col mdl-typography--body-1">Watched&nbsp;<a href="https://music.youtube.com/watch?v=a">Song</a><br><a href="https://www.youtube.com/channel/b">Band</a><br>{}<br></div></div></div>"#,
+            date
+        )
+    };
+    let html = [
+        cell("Jan 15, 2024, 8:00:00 PM GMT+05:30"),
+        cell("15 Jan 2024, 20:00:00 GMT-3"),
+        cell("Jan 15, 2024, 8:00:00 PM +0100"),
+        cell("Jan 15, 2024, 8:00:00 PM AEDT"),
+    ]
+    .concat();
+
+    let history = parse_html(&html);
+
+    let times: Vec<String> = history
+        .scrobbles
+        .iter()
+        .map(|s| s.played_at.to_rfc3339())
+        .collect();
+    assert_eq!(
+        times,
+        vec![
+            "2024-01-15T14:30:00+00:00",
+            "2024-01-15T23:00:00+00:00",
+            "2024-01-15T19:00:00+00:00",
+        ]
+    );
+    assert_eq!(history.undated, 1);
+}
+
+#[test]
+fn counts_json_entries_with_unreadable_times() {
+    let json = r#"[
+        {
+            "header": "YouTube Music",
+            "title": "Watched Song Title",
+            "titleUrl": "https://music.youtube.com/watch?v=abc",
+            "time": "yesterday"
+        },
+        {
+            "header": "YouTube Music",
+            "title": "Watched Other Song",
+            "titleUrl": "https://music.youtube.com/watch?v=def",
+            "time": "2024-01-15T12:34:56Z"
+        }
+    ]"#;
+
+    let history = parse_json(json).unwrap();
+
+    assert_eq!(history.scrobbles.len(), 1);
+    assert_eq!(history.scrobbles[0].track.title, "Other Song");
+    assert_eq!(history.undated, 1);
+}
+
+#[test]
+fn fails_on_malformed_json() {
+    assert!(parse_json(r#"{"header": "YouTube Music"}"#).is_err());
+}
+
+#[test]
+fn reads_both_formats_from_folder() {
+    let dir = std::env::temp_dir().join("music-stats-takeout");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("watch-history.json"), HISTORY_JSON).unwrap();
+    std::fs::write(dir.join("watch-history.html"), HISTORY_HTML).unwrap();
+    std::fs::write(dir.join("search-history.json"), "not read").unwrap();
+
+    let scrobbles = read_history(&YouTubeTakeoutConfig { paths: vec![dir] }).unwrap();
+
+    assert_eq!(scrobbles.len(), 4);
+}